@@ -1,25 +1,38 @@
 use ropey::Rope;
 use serde_json::Value;
 
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use tokio::sync::RwLock;
 
 use tower_lsp_server::jsonrpc::Result;
 use tower_lsp_server::lsp_types::{
-    CompletionOptions, CompletionParams, CompletionResponse, Diagnostic,
-    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
-    DidChangeWorkspaceFoldersParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
-    DidSaveTextDocumentParams, ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse,
-    Hover, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
-    InitializedParams, Location, MarkupContent, MarkupKind, MessageType, OneOf, Range,
-    ReferenceParams, SaveOptions, SemanticTokensParams, SemanticTokensResult, ServerCapabilities,
-    TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
-    TextDocumentSyncSaveOptions, Uri, WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities,
+    CodeActionKind, CodeActionOptions, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CompletionItem, CompletionOptions,
+    CompletionParams, CompletionResponse, Diagnostic, DidChangeConfigurationParams,
+    DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidChangeWatchedFilesRegistrationOptions, DidChangeWorkspaceFoldersParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DocumentFormattingParams, DocumentOnTypeFormattingOptions, DocumentOnTypeFormattingParams,
+    DocumentRangeFormattingParams, ExecuteCommandParams, FileSystemWatcher, FoldingRange,
+    FoldingRangeParams, FoldingRangeProviderCapability, FormattingOptions, GlobPattern,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverParams, HoverProviderCapability,
+    InitializeParams, InitializeResult, InitializedParams, Location, MarkupContent, MarkupKind,
+    MessageType, OneOf, Position, Range, ReferenceParams, Registration, SaveOptions,
+    SelectionRange, SelectionRangeParams, SelectionRangeProviderCapability, SemanticTokens,
+    SemanticTokensDelta, SemanticTokensDeltaParams, SemanticTokensFullDeltaResult,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensRangeParams, SemanticTokensRangeResult, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, TextEdit, Uri,
+    WorkDoneProgressOptions, WorkspaceFolder, WorkspaceFoldersServerCapabilities,
     WorkspaceServerCapabilities,
 };
-use tower_lsp_server::{Client, LanguageServer};
+use tower_lsp_server::{Client, LanguageServer, UriExt};
 
 use simplicityhl::{
     ast,
@@ -28,18 +41,38 @@ use simplicityhl::{
     parse::ParseFromStr,
 };
 
-use crate::completion::{self, CompletionProvider};
+use crate::code_action::{doc_stub, extract, inline, literal, missing_const, quick_fix};
+use crate::completion::{
+    self, CompletionProvider,
+    builtin::CallSite,
+    context::{ExpectedType, SyntaxContext},
+    fuzzy::Candidate,
+    snippet::{self, Snippets},
+};
 use crate::error::LspError;
+use crate::folding;
+use crate::format::{self, FormatOptions};
 use crate::function::Functions;
+use crate::lint;
+use crate::scope;
+use crate::selection;
+use crate::semantic::{self, CachedTokens};
+use crate::type_utils::{Aliases, resolve_alias};
 use crate::utils::{
-    find_all_references, find_function_name_range, find_related_call, get_call_span,
-    get_comments_from_lines, position_to_span, span_contains, span_to_positions,
+    byte_to_position, document_end, find_all_references, find_function_name_range,
+    find_related_call, get_call_span, get_comments_from_lines, position_to_byte, position_to_span,
+    span_contains, span_to_positions,
 };
 
 #[derive(Debug)]
 struct Document {
     functions: Functions,
+    aliases: Aliases,
+    /// Number of calls of each function, jet and builtin, keyed by completion label.
+    usage: HashMap<String, usize>,
     text: Rope,
+    /// Version of the text, which is kept when the document is saved.
+    version: Option<i32>,
 }
 
 #[derive(Debug)]
@@ -49,6 +82,32 @@ pub struct Backend {
     document_map: Arc<RwLock<HashMap<Uri, Document>>>,
 
     completion_provider: CompletionProvider,
+
+    /// Root directories of the workspace folders, where snippet packs are loaded from.
+    workspace_roots: Arc<RwLock<Vec<PathBuf>>>,
+
+    /// Whether the client supports registering file watchers after initialization.
+    watch_files: AtomicBool,
+
+    /// Snippets loaded from the workspace.
+    snippets: Arc<RwLock<Snippets>>,
+
+    /// Formatter options set by the configuration. Indentation is taken from each request.
+    format_options: Arc<RwLock<FormatOptions>>,
+
+    /// Semantic tokens last sent for each document, which delta requests are computed from.
+    semantic_tokens: Arc<RwLock<HashMap<Uri, CachedTokens>>>,
+
+    /// Counter of computed semantic tokens, so every result has a distinct `result_id`.
+    semantic_tokens_results: AtomicU64,
+}
+
+/// Document and cursor position, which completion helpers of [`Backend`] look at.
+struct CompletionCursor<'a> {
+    doc: &'a Document,
+    text: &'a str,
+    position: Position,
+    expected: Option<ExpectedType<'a>>,
 }
 
 struct TextDocumentItem<'a> {
@@ -58,7 +117,27 @@ struct TextDocumentItem<'a> {
 }
 
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        #[allow(deprecated)]
+        let root_uri = params.root_uri;
+        let uris = match params.workspace_folders {
+            Some(folders) => folders.into_iter().map(|folder| folder.uri).collect(),
+            None => root_uri.into_iter().collect::<Vec<_>>(),
+        };
+        *self.workspace_roots.write().await = uris
+            .iter()
+            .filter_map(|uri| uri.to_file_path().map(Cow::into_owned))
+            .collect();
+
+        let watch_files = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+            .and_then(|capability| capability.dynamic_registration)
+            .unwrap_or(false);
+        self.watch_files.store(watch_files, Ordering::Relaxed);
+
         Ok(InitializeResult {
             server_info: None,
             capabilities: ServerCapabilities {
@@ -73,7 +152,7 @@ impl LanguageServer for Backend {
                     },
                 )),
                 completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
+                    resolve_provider: Some(true),
                     trigger_characters: Some(vec![":".to_string()]),
                     work_done_progress_options: WorkDoneProgressOptions::default(),
                     all_commit_characters: None,
@@ -89,28 +168,91 @@ impl LanguageServer for Backend {
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "}".to_string(),
+                    more_trigger_character: Some(vec![";".to_string()]),
+                }),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic::legend(),
+                            range: Some(true),
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                            ..SemanticTokensOptions::default()
+                        },
+                    ),
+                ),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::QUICKFIX,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::REFACTOR_INLINE,
+                            CodeActionKind::REFACTOR_REWRITE,
+                        ]),
+                        work_done_progress_options: WorkDoneProgressOptions::default(),
+                        resolve_provider: None,
+                    },
+                )),
                 ..ServerCapabilities::default()
             },
         })
     }
 
-    async fn initialized(&self, _: InitializedParams) {}
+    async fn initialized(&self, _: InitializedParams) {
+        self.watch_snippets().await;
+        self.reload_snippets().await;
+    }
 
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
 
-    async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {}
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let path = |folder: WorkspaceFolder| folder.uri.to_file_path().map(Cow::into_owned);
+        {
+            let mut roots = self.workspace_roots.write().await;
+            let removed: Vec<PathBuf> = params.event.removed.into_iter().filter_map(path).collect();
+            roots.retain(|root| !removed.contains(root));
+            for root in params.event.added.into_iter().filter_map(path) {
+                if !roots.contains(&root) {
+                    roots.push(root);
+                }
+            }
+        }
+        self.reload_snippets().await;
+    }
 
-    async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {}
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        if let Some(max_width) = params
+            .settings
+            .pointer("/simplicityhl/format/maxWidth")
+            .and_then(Value::as_u64)
+            .and_then(|width| usize::try_from(width).ok())
+        {
+            self.format_options.write().await.max_width = max_width;
+        }
+    }
 
-    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {}
+    async fn did_change_watched_files(&self, _: DidChangeWatchedFilesParams) {
+        // Only snippet packs are watched, so any change requires reloading them.
+        self.reload_snippets().await;
+    }
 
     async fn execute_command(&self, _: ExecuteCommandParams) -> Result<Option<Value>> {
         Ok(None)
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        // Versions of a reopened document start over, so tokens of the previous one are stale.
+        self.semantic_tokens
+            .write()
+            .await
+            .remove(&params.text_document.uri);
         self.on_change(TextDocumentItem {
             uri: params.text_document.uri,
             text: &params.text_document.text,
@@ -139,13 +281,88 @@ impl LanguageServer for Backend {
         }
     }
 
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {}
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.semantic_tokens
+            .write()
+            .await
+            .remove(&params.text_document.uri);
+    }
 
     async fn semantic_tokens_full(
         &self,
-        _: SemanticTokensParams,
+        params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        Ok(None)
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        let tokens = self.cached_semantic_tokens(uri, doc).await;
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: Some(tokens.result_id),
+            data: tokens.data,
+        })))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        let previous = self
+            .semantic_tokens
+            .read()
+            .await
+            .get(uri)
+            .filter(|tokens| tokens.result_id == params.previous_result_id)
+            .cloned();
+        let tokens = self.cached_semantic_tokens(uri, doc).await;
+
+        // Tokens of unknown result are sent in full.
+        let Some(previous) = previous else {
+            return Ok(Some(SemanticTokensFullDeltaResult::Tokens(
+                SemanticTokens {
+                    result_id: Some(tokens.result_id),
+                    data: tokens.data,
+                },
+            )));
+        };
+        Ok(Some(SemanticTokensFullDeltaResult::TokensDelta(
+            SemanticTokensDelta {
+                edits: semantic::delta(&previous.data, &tokens.data),
+                result_id: Some(tokens.result_id),
+            },
+        )))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        Ok(Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: semantic::semantic_tokens_in_range(
+                &doc.text.to_string(),
+                &doc.functions,
+                &doc.aliases,
+                params.range,
+            ),
+        })))
     }
 
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
@@ -165,7 +382,9 @@ impl LanguageServer for Backend {
             .ok_or(LspError::Internal("Rope proccesing error".into()))?;
 
         let slice = line
-            .get_slice(..pos.character as usize)
+            .try_utf16_cu_to_char(pos.character as usize)
+            .ok()
+            .and_then(|end| line.get_slice(..end))
             .ok_or(LspError::ConversionFailed(
                 "Rope to slice conversion failed".into(),
             ))?;
@@ -174,30 +393,194 @@ impl LanguageServer for Backend {
             "RopeSlice to str conversion failed".into(),
         ))?;
 
-        let trimmed_prefix = prefix.trim_end();
-
-        if let Some(last) = trimmed_prefix
-            .rsplit(|c: char| !c.is_alphanumeric() && c != ':')
+        let word = prefix
+            .rsplit(|c: char| !c.is_alphanumeric() && c != '_')
             .next()
-        {
-            if last.starts_with("jet:::") {
-                return Ok(Some(CompletionResponse::Array(vec![])));
-            } else if last == "jet::" || last.starts_with("jet::") {
-                return Ok(Some(CompletionResponse::Array(
-                    self.completion_provider.jets().to_vec(),
-                )));
-            }
-        // Completion after a colon is needed only for jets.
-        } else if trimmed_prefix.ends_with(':') {
-            return Ok(Some(CompletionResponse::Array(vec![])));
+            .unwrap_or_default();
+        let usage = workspace_usage(&documents);
+
+        let text = doc.text.to_string();
+        let cursor = CompletionCursor {
+            doc,
+            text: &text,
+            position: pos,
+            expected: completion::context::expected_type(&text, pos, &doc.functions).map(|ty| {
+                ExpectedType {
+                    ty,
+                    aliases: &doc.aliases,
+                }
+            }),
+        };
+        let snippets = self.snippets.read().await;
+
+        let completions = self
+            .type_position_completions(&cursor)
+            .or_else(|| self.module_path_completions(&cursor, prefix))
+            .or_else(|| Self::combinator_argument_completions(&cursor))
+            .or_else(|| Self::match_arm_completions(&cursor))
+            .unwrap_or_else(|| self.general_completions(&cursor, &snippets));
+
+        Ok(Some(self.completion_provider.filter(
+            completions,
+            word,
+            &usage,
+        )))
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        Ok(self.completion_provider.resolve(item))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        let text = doc.text.to_string();
+        let options = self.format_options(&params.options).await;
+
+        let Some(formatted) = format::format(&text, &options) else {
+            return Ok(None);
+        };
+        if formatted == text {
+            return Ok(Some(vec![]));
         }
 
-        let mut completions =
-            CompletionProvider::get_function_completions(&doc.functions.functions_and_docs());
-        completions.extend_from_slice(self.completion_provider.builtins());
-        completions.extend_from_slice(self.completion_provider.modules());
+        Ok(Some(vec![TextEdit::new(
+            Range::new(Position::new(0, 0), document_end(&doc.text)),
+            formatted,
+        )]))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        let range = position_to_byte(&doc.text, params.range.start)
+            ..position_to_byte(&doc.text, params.range.end);
+        let options = self.format_options(&params.options).await;
+
+        Ok(format_items(&doc.text, range, &options))
+    }
 
-        Ok(Some(CompletionResponse::Array(completions)))
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document_position.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        // The cursor is right after the typed character, which ends the statement to re-indent.
+        let offset = position_to_byte(&doc.text, params.text_document_position.position);
+        let options = self.format_options(&params.options).await;
+
+        let edits = format::reindent_statement(&doc.text.to_string(), offset, &options);
+        Ok(Some(byte_edits(&doc.text, edits)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        let mut actions: Vec<_> = params
+            .context
+            .diagnostics
+            .iter()
+            .flat_map(|diagnostic| {
+                quick_fix::type_mismatch_fixes(uri, &doc.text, diagnostic, &doc.aliases)
+            })
+            .collect();
+        actions.extend(extract::extract_actions(
+            uri,
+            &doc.text,
+            params.range,
+            &doc.functions,
+            &doc.aliases,
+        ));
+        actions.extend(inline::inline_actions(
+            uri,
+            &doc.text,
+            params.range.start,
+            &doc.functions,
+            &doc.aliases,
+        ));
+        actions.extend(literal::literal_actions(
+            uri,
+            &doc.text,
+            params.range.start,
+            &doc.functions,
+            &doc.aliases,
+        ));
+        actions.extend(missing_const::missing_const_actions(
+            uri,
+            &doc.text,
+            params.range.start,
+            &doc.functions,
+            &doc.aliases,
+        ));
+        actions.extend(doc_stub::doc_stub_actions(
+            uri,
+            &doc.text,
+            params.range.start,
+            &doc.functions,
+            &params.context.diagnostics,
+        ));
+
+        Ok(Some(
+            actions
+                .into_iter()
+                .map(CodeActionOrCommand::CodeAction)
+                .collect(),
+        ))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        Ok(Some(folding::folding_ranges(&doc.text.to_string())))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let documents = self.document_map.read().await;
+        let uri = &params.text_document.uri;
+
+        let doc = documents
+            .get(uri)
+            .ok_or(LspError::DocumentNotFound(uri.to_owned()))?;
+
+        Ok(Some(
+            params
+                .positions
+                .into_iter()
+                .map(|position| selection::selection_range(&doc.text, &doc.functions, position))
+                .collect(),
+        ))
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
@@ -211,13 +594,13 @@ impl LanguageServer for Backend {
 
         let token_pos = params.text_document_position_params.position;
 
-        let token_span = position_to_span(token_pos)?;
+        let token_span = position_to_span(&doc.text, token_pos)?;
         let Ok(Some(call)) = find_related_call(&functions, token_span) else {
             return Ok(None);
         };
 
         let call_span = get_call_span(call)?;
-        let (start, end) = span_to_positions(&call_span)?;
+        let (start, end) = span_to_positions(&doc.text, &call_span);
 
         let description = match call.name() {
             parse::CallName::Jet(jet) => {
@@ -227,7 +610,8 @@ impl LanguageServer for Backend {
 
                 let template = completion::jet::jet_to_template(element);
                 format!(
-                    "Jet function\n```simplicityhl\nfn {}({}) -> {}\n```\n---\n\n{}",
+                    "Jet function · {}\n```simplicityhl\nfn {}({}) -> {}\n```\n---\n\n{}",
+                    completion::jet::category(element),
                     template.display_name,
                     template.args.join(", "),
                     template.return_type,
@@ -252,12 +636,16 @@ impl LanguageServer for Backend {
                 )
             }
             other => {
-                let Some(template) = completion::builtin::match_callname(other) else {
+                let Some(template) = completion::builtin::instantiate_callname(
+                    other,
+                    &doc.functions,
+                    &call_site(doc, call),
+                ) else {
                     return Ok(None);
                 };
                 format!(
                     "Built-in function\n```simplicityhl\nfn {}({}) -> {}\n```\n---\n{}",
-                    template.display_name,
+                    template.get_generic_name(),
                     template.args.join(", "),
                     template.return_type,
                     template.description
@@ -287,7 +675,7 @@ impl LanguageServer for Backend {
         let functions = doc.functions.functions();
 
         let token_position = params.text_document_position_params.position;
-        let token_span = position_to_span(token_position)?;
+        let token_span = position_to_span(&doc.text, token_position)?;
 
         let Ok(Some(call)) = find_related_call(&functions, token_span) else {
             let Some(func) = functions
@@ -316,7 +704,7 @@ impl LanguageServer for Backend {
                             "Function {func} is not found"
                         )))?;
 
-                let (start, end) = span_to_positions(function.as_ref())?;
+                let (start, end) = span_to_positions(&doc.text, function.as_ref());
                 Ok(Some(GotoDefinitionResponse::from(Location::new(
                     uri.clone(),
                     Range::new(start, end),
@@ -337,7 +725,7 @@ impl LanguageServer for Backend {
 
         let token_position = params.text_document_position.position;
 
-        let token_span = position_to_span(token_position)?;
+        let token_span = position_to_span(&doc.text, token_position)?;
 
         let call_name =
             find_related_call(&functions, token_span)?.map(simplicityhl::parse::Call::name);
@@ -346,7 +734,7 @@ impl LanguageServer for Backend {
             Some(parse::CallName::Custom(_)) | None => {}
             Some(name) => {
                 return Ok(Some(
-                    find_all_references(&functions, name)?
+                    find_all_references(&doc.text, &functions, name)
                         .iter()
                         .map(|range| Location {
                             range: *range,
@@ -368,14 +756,18 @@ impl LanguageServer for Backend {
 
         if (token_position <= range.end && token_position >= range.start) || call_name.is_some() {
             Ok(Some(
-                find_all_references(&functions, &parse::CallName::Custom(func.name().clone()))?
-                    .into_iter()
-                    .chain(std::iter::once(range))
-                    .map(|range| Location {
-                        range,
-                        uri: uri.clone(),
-                    })
-                    .collect(),
+                find_all_references(
+                    &doc.text,
+                    &functions,
+                    &parse::CallName::Custom(func.name().clone()),
+                )
+                .into_iter()
+                .chain(std::iter::once(range))
+                .map(|range| Location {
+                    range,
+                    uri: uri.clone(),
+                })
+                .collect(),
             ))
         } else {
             Ok(None)
@@ -389,62 +781,356 @@ impl Backend {
             client,
             document_map: Arc::new(RwLock::new(HashMap::new())),
             completion_provider: CompletionProvider::new(),
+            workspace_roots: Arc::new(RwLock::new(Vec::new())),
+            watch_files: AtomicBool::new(false),
+            snippets: Arc::new(RwLock::new(Snippets::default())),
+            format_options: Arc::new(RwLock::new(FormatOptions::default())),
+            semantic_tokens: Arc::new(RwLock::new(HashMap::new())),
+            semantic_tokens_results: AtomicU64::new(0),
+        }
+    }
+
+    /// Complete builtin types and type aliases in a type position.
+    fn type_position_completions<'a>(
+        &'a self,
+        cursor: &CompletionCursor<'a>,
+    ) -> Option<Vec<Candidate<'a>>> {
+        if !completion::context::is_type_position(cursor.text, cursor.position) {
+            return None;
+        }
+        let aliases = completion::builtin_type::get_alias_completions(&cursor.doc.aliases);
+        Some(
+            self.completion_provider
+                .types()
+                .chain(aliases.into_iter().map(Candidate::from))
+                .collect(),
+        )
+    }
+
+    /// Complete jets after `jet::` and constants after `witness::` or `param::`.
+    fn module_path_completions<'a>(
+        &'a self,
+        cursor: &CompletionCursor<'a>,
+        prefix: &str,
+    ) -> Option<Vec<Candidate<'a>>> {
+        let last = prefix
+            .trim_end()
+            .rsplit(|c: char| !c.is_alphanumeric() && c != ':' && c != '_')
+            .next()?;
+
+        if last.starts_with("jet:::") {
+            return Some(Vec::new());
+        }
+        if last.starts_with("jet::") {
+            return Some(
+                self.completion_provider
+                    .jets(cursor.expected.as_ref())
+                    .collect(),
+            );
+        }
+        let module = ["witness", "param"]
+            .into_iter()
+            .find(|module| last.starts_with(&format!("{module}::")))?;
+        Some(completion::get_binding_completions(
+            &scope::module_constants(cursor.text, module),
+            cursor.expected.as_ref(),
+        ))
+    }
+
+    /// Complete the bound function and the size in generic arguments of `fold`, `array_fold` and
+    /// `for_while`.
+    fn combinator_argument_completions(
+        cursor: &CompletionCursor,
+    ) -> Option<Vec<Candidate<'static>>> {
+        let (combinator, index) =
+            completion::context::combinator_argument(cursor.text, cursor.position)?;
+        let doc = cursor.doc;
+
+        let completions = if index == 0 {
+            completion::combinator::get_function_completions(
+                combinator,
+                &doc.functions.functions_and_docs(),
+                &doc.aliases,
+            )
+        } else {
+            completion::combinator::get_size_completions(
+                combinator,
+                &scope::bindings_at(cursor.text, cursor.position, &doc.aliases),
+                &doc.aliases,
+            )
+        };
+        Some(owned(completions))
+    }
+
+    /// Complete missing arms of the `match` expression, which arm starts at the cursor.
+    fn match_arm_completions(cursor: &CompletionCursor) -> Option<Vec<Candidate<'static>>> {
+        let doc = cursor.doc;
+        let arms = completion::context::match_arms(cursor.text, cursor.position)?;
+        let ty =
+            completion::pattern::scrutinee_type(cursor.text, &arms, &doc.functions, &doc.aliases)?;
+        Some(owned(completion::pattern::get_arm_completions(
+            &ty,
+            &arms.written,
+            &doc.aliases,
+        )))
+    }
+
+    /// Complete everything valid at the cursor: bindings, functions, constructors, library
+    /// functions, builtins, snippets, modules and keywords. Between items only keywords are valid.
+    fn general_completions<'a>(
+        &'a self,
+        cursor: &CompletionCursor<'a>,
+        snippets: &'a Snippets,
+    ) -> Vec<Candidate<'a>> {
+        let syntax = completion::context::syntax_context(cursor.text, cursor.position);
+        if matches!(syntax, SyntaxContext::TopLevel | SyntaxContext::Module) {
+            return owned(completion::keyword::get_keyword_completions(syntax));
+        }
+
+        let doc = cursor.doc;
+        let expected = cursor.expected.as_ref();
+        let mut completions = completion::get_binding_completions(
+            &scope::bindings_at(cursor.text, cursor.position, &doc.aliases),
+            expected,
+        );
+        completions.extend(CompletionProvider::get_function_completions(
+            &doc.functions.functions_and_docs(),
+            expected,
+        ));
+        if let Some(expected) = expected {
+            completions.extend(
+                completion::pattern::get_constructor_completions(expected)
+                    .into_iter()
+                    .map(|item| Candidate {
+                        item: Cow::Owned(item),
+                        fits_type: true,
+                    }),
+            );
+        }
+        completions.extend(self.completion_provider.library(
+            cursor.text,
+            completion::library::definition_position(cursor.text, cursor.position),
+            expected,
+        ));
+        completions.extend(owned(completion::keyword::get_keyword_completions(syntax)));
+        completions.extend(self.completion_provider.builtins(&doc.functions, expected));
+        completions.extend(snippets.items.iter().map(Candidate::from));
+        completions.extend(self.completion_provider.modules());
+        completions
+    }
+
+    /// Combine indentation of the request with formatter options set by the configuration.
+    async fn format_options(&self, options: &FormattingOptions) -> FormatOptions {
+        FormatOptions {
+            indent_width: options.tab_size as usize,
+            use_tabs: !options.insert_spaces,
+            ..*self.format_options.read().await
+        }
+    }
+
+    /// Ask the client to notify the server about changes of snippet packs, if it supports
+    /// registering file watchers dynamically.
+    async fn watch_snippets(&self) {
+        if !self.watch_files.load(Ordering::Relaxed) {
+            return;
+        }
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String(snippet::SNIPPETS_GLOB.to_string()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "snippets".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to watch snippet packs: {err}"),
+                )
+                .await;
         }
     }
 
+    /// Load snippet packs of the workspace and publish diagnostics of files, which can't be parsed.
+    async fn reload_snippets(&self) {
+        let loaded = snippet::load(&self.workspace_roots.read().await);
+
+        // Clear diagnostics of files, which were removed since the previous load.
+        let removed: Vec<Uri> = self
+            .snippets
+            .read()
+            .await
+            .files
+            .iter()
+            .filter(|(uri, _)| !loaded.files.iter().any(|(loaded, _)| loaded == uri))
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        for uri in removed {
+            self.client.publish_diagnostics(uri, vec![], None).await;
+        }
+        for (uri, diagnostics) in &loaded.files {
+            self.client
+                .publish_diagnostics(uri.clone(), diagnostics.clone(), None)
+                .await;
+        }
+
+        *self.snippets.write().await = loaded;
+    }
+
+    /// Return semantic tokens of the document, which are computed once for every version and
+    /// kept for delta requests.
+    ///
+    /// Documents without a version, e.g. after `did_save`, are computed on every request.
+    async fn cached_semantic_tokens(&self, uri: &Uri, doc: &Document) -> CachedTokens {
+        let mut cache = self.semantic_tokens.write().await;
+        if let Some(tokens) = cache
+            .get(uri)
+            .filter(|tokens| doc.version.is_some() && tokens.version == doc.version)
+        {
+            return tokens.clone();
+        }
+
+        let result = self.semantic_tokens_results.fetch_add(1, Ordering::Relaxed);
+        let tokens = CachedTokens {
+            result_id: result.to_string(),
+            version: doc.version,
+            data: semantic::semantic_tokens(&doc.text.to_string(), &doc.functions, &doc.aliases),
+        };
+        cache.insert(uri.clone(), tokens.clone());
+        tokens
+    }
+
     /// Function which executed on change of file (`did_save`, `did_open` or `did_change` methods)
     async fn on_change(&self, params: TextDocumentItem<'_>) {
         let (err, document) = parse_program(params.text);
+        let mut diagnostics = document
+            .as_ref()
+            .map(|doc| lint::missing_docs(&doc.functions, &doc.text))
+            .unwrap_or_default();
 
         let mut documents = self.document_map.write().await;
+        let version = params
+            .version
+            .or_else(|| documents.get(&params.uri).and_then(|doc| doc.version));
         if let Some(doc) = document {
-            documents.insert(params.uri.clone(), doc);
+            documents.insert(params.uri.clone(), Document { version, ..doc });
         } else if let Some(doc) = documents.get_mut(&params.uri) {
             doc.text = Rope::from_str(params.text);
+            doc.version = version;
         }
 
         match err {
             None => {
                 self.client
-                    .publish_diagnostics(params.uri.clone(), vec![], params.version)
+                    .publish_diagnostics(params.uri.clone(), diagnostics, params.version)
                     .await;
             }
             Some(err) => {
-                let (start, end) = match span_to_positions(err.span()) {
-                    Ok(result) => result,
-                    Err(err) => {
-                        self.client
-                            .log_message(
-                                MessageType::ERROR,
-                                format!("Catch error while parsing span: {err}"),
-                            )
-                            .await;
-                        return;
-                    }
-                };
+                let (start, end) = span_to_positions(&Rope::from_str(params.text), err.span());
 
+                diagnostics.push(Diagnostic::new_simple(
+                    Range::new(start, end),
+                    err.error().to_string(),
+                ));
                 self.client
-                    .publish_diagnostics(
-                        params.uri.clone(),
-                        vec![Diagnostic::new_simple(
-                            Range::new(start, end),
-                            err.error().to_string(),
-                        )],
-                        params.version,
-                    )
+                    .publish_diagnostics(params.uri.clone(), diagnostics, params.version)
                     .await;
             }
         }
     }
 }
 
+/// Format top-level items of the document, which overlap byte range `range`, see
+/// [`format::format_items`].
+fn format_items(
+    rope: &Rope,
+    range: std::ops::Range<usize>,
+    options: &FormatOptions,
+) -> Option<Vec<TextEdit>> {
+    let edits = format::format_items(&rope.to_string(), range, options)?;
+    Some(byte_edits(rope, edits))
+}
+
+/// Convert edits of byte ranges to [`TextEdit`].
+fn byte_edits(rope: &Rope, edits: Vec<(std::ops::Range<usize>, String)>) -> Vec<TextEdit> {
+    edits
+        .into_iter()
+        .map(|(range, text)| {
+            TextEdit::new(
+                Range::new(
+                    byte_to_position(rope, range.start),
+                    byte_to_position(rope, range.end),
+                ),
+                text,
+            )
+        })
+        .collect()
+}
+
+/// Wrap items built for a single request, which don't depend on the expected type, so they can
+/// be ranked together with borrowed ones.
+fn owned(items: Vec<CompletionItem>) -> Vec<Candidate<'static>> {
+    items.into_iter().map(Candidate::from).collect()
+}
+
+/// Sum calls of each function, jet and builtin over all open documents.
+fn workspace_usage(documents: &HashMap<Uri, Document>) -> HashMap<String, usize> {
+    let mut usage = HashMap::new();
+    for document in documents.values() {
+        for (label, count) in &document.usage {
+            *usage.entry(label.clone()).or_default() += count;
+        }
+    }
+    usage
+}
+
+/// Find types of the first argument and of the result of `call`, which are not written in it.
+fn call_site(doc: &Document, call: &parse::Call) -> CallSite {
+    let text = doc.text.to_string();
+    let (start, _) = span_to_positions(&doc.text, call.span());
+
+    let argument = call.args().first().and_then(|arg| {
+        let (arg_start, arg_end) = span_to_positions(&doc.text, arg.as_ref());
+        let expression =
+            &text[position_to_byte(&doc.text, arg_start)..position_to_byte(&doc.text, arg_end)];
+        completion::context::expression_type(
+            &text,
+            expression,
+            arg_start,
+            &doc.functions,
+            &doc.aliases,
+        )
+    });
+
+    CallSite {
+        argument: argument.map(|ty| resolve_alias(&ty, &doc.aliases)),
+        expected: completion::context::expected_type(&text, start, &doc.functions),
+    }
+}
+
 /// Create [`Document`] using parsed program and code.
 fn create_document(program: &simplicityhl::parse::Program, text: &str) -> Document {
     let mut document = Document {
         functions: Functions::new(),
+        aliases: Aliases::new(),
+        usage: HashMap::new(),
         text: Rope::from_str(text),
+        version: None,
     };
 
+    program.items().iter().for_each(|item| {
+        if let parse::Item::TypeAlias(alias) = item {
+            document
+                .aliases
+                .insert(alias.name().to_string(), alias.ty().to_string());
+        }
+    });
+
     program
         .items()
         .iter()
@@ -465,6 +1151,8 @@ fn create_document(program: &simplicityhl::parse::Program, text: &str) -> Docume
             );
         });
 
+    document.usage = completion::count_calls(&document.functions.functions());
+
     document
 }
 
@@ -485,6 +1173,7 @@ fn parse_program(text: &str) -> (Option<RichError>, Option<Document>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use miniscript::iter::TreeLike;
 
     fn sample_program() -> &'static str {
         "fn add(a: u32, b: u32) -> u32 { let (_, res): (bool, u32) = jet::add_32(a, b); res }
@@ -528,4 +1217,33 @@ mod tests {
         );
         assert!(doc.is_none(), "Expected no document to return");
     }
+
+    #[test]
+    fn test_create_document_aliases() {
+        let (_, doc) = parse_program("type Amount = u64;\nfn main() {}");
+        let doc = doc.expect("Expected Some(Document)");
+        assert_eq!(doc.aliases.get("Amount").map(String::as_str), Some("u64"));
+    }
+
+    #[test]
+    fn test_call_site() {
+        let text = "fn main() {
+    let x: Either<u8, u16> = Left(1);
+    let y: u8 = unwrap_left::<u16>(x);
+}";
+        let (_, doc) = parse_program(text);
+        let doc = doc.expect("Expected Some(Document)");
+        let functions = doc.functions.functions();
+        let call = parse::ExprTree::Expression(functions[0].body())
+            .pre_order_iter()
+            .find_map(|expr| match expr {
+                parse::ExprTree::Call(call) => Some(call),
+                _ => None,
+            })
+            .expect("Expected call of `unwrap_left`");
+
+        let site = call_site(&doc, call);
+        assert_eq!(site.argument.as_deref(), Some("Either<u8,u16>"));
+        assert_eq!(site.expected.as_deref(), Some("u8"));
+    }
 }
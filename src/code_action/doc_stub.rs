@@ -0,0 +1,99 @@
+use ropey::Rope;
+use simplicityhl::parse::Function;
+use tower_lsp_server::lsp_types::{
+    CodeAction, CodeActionKind, Diagnostic, NumberOrString, Position, Range, TextEdit, Uri,
+};
+
+use crate::code_action::code_action;
+use crate::function::Functions;
+use crate::lint::MISSING_DOCS;
+use crate::utils::span_to_positions;
+
+/// Get action inserting documentation comment above the undocumented function, which signature
+/// starts on the line of `position`.
+pub fn doc_stub_actions(
+    uri: &Uri,
+    rope: &Rope,
+    position: Position,
+    functions: &Functions,
+    diagnostics: &[Diagnostic],
+) -> Vec<CodeAction> {
+    functions
+        .functions_and_docs()
+        .into_iter()
+        .filter(|(_, doc)| doc.is_empty())
+        .filter_map(|(func, _)| {
+            let (start, _) = span_to_positions(rope, func.as_ref());
+            if start.line != position.line {
+                return None;
+            }
+
+            let indent: String = rope
+                .get_line(start.line as usize)?
+                .chars()
+                .take_while(|ch| *ch == ' ' || *ch == '\t')
+                .collect();
+            let stub: String = doc_stub(func)
+                .lines()
+                .map(|line| format!("{indent}{line}\n"))
+                .collect();
+            let insert = Position::new(start.line, 0);
+
+            let lint = diagnostics.iter().find(|diagnostic| {
+                diagnostic.code == Some(NumberOrString::String(MISSING_DOCS.to_string()))
+                    && diagnostic.range.start.line == start.line
+            });
+            Some(CodeAction {
+                diagnostics: lint.map(|diagnostic| vec![diagnostic.clone()]),
+                ..code_action(
+                    format!("Generate documentation of `{}`", func.name()),
+                    CodeActionKind::QUICKFIX,
+                    uri,
+                    vec![TextEdit::new(Range::new(insert, insert), stub)],
+                )
+            })
+        })
+        .collect()
+}
+
+/// Return `///` template listing parameters and return type of the function.
+fn doc_stub(func: &Function) -> String {
+    let mut lines = vec![format!("/// Describe `{}`.", func.name())];
+
+    if !func.params().is_empty() {
+        lines.push("///".to_string());
+        for param in func.params() {
+            let param = param.to_string();
+            lines.push(match param.split_once(':') {
+                Some((name, ty)) => format!("/// - `{}`: `{}`", name.trim(), ty.trim()),
+                None => format!("/// - `{param}`"),
+            });
+        }
+    }
+    if let Some(ret) = func.ret() {
+        lines.push("///".to_string());
+        lines.push(format!("/// Returns `{ret}`."));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simplicityhl::parse::{self, ParseFromStr};
+
+    #[test]
+    fn test_doc_stub() {
+        let program = parse::Program::parse_from_str("fn add(a: u32, b: u32) -> u32 { a }")
+            .expect("Expected valid program");
+        let parse::Item::Function(func) = &program.items()[0] else {
+            panic!("Expected function");
+        };
+
+        assert_eq!(
+            doc_stub(func),
+            "/// Describe `add`.\n///\n/// - `a`: `u32`\n/// - `b`: `u32`\n///\n/// Returns `u32`."
+        );
+    }
+}
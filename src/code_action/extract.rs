@@ -0,0 +1,307 @@
+use miniscript::iter::TreeLike;
+use ropey::Rope;
+use simplicityhl::error::Span;
+use simplicityhl::parse::{self, ParseFromStr};
+use tower_lsp_server::lsp_types::{CodeAction, CodeActionKind, Range, TextEdit, Uri};
+
+use crate::code_action::{code_action, indentation, unique_name};
+use crate::completion::{context, library};
+use crate::function::Functions;
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope::{self, Binding, BindingKind};
+use crate::type_utils::Aliases;
+use crate::utils::{byte_to_position, position_to_byte, position_to_span, span_contains};
+
+/// Get refactorings of the expression selected by `range`: extract it into a `let` binding
+/// before the enclosing statement, or into a new function taking the variables it uses.
+pub fn extract_actions(
+    uri: &Uri,
+    rope: &Rope,
+    range: Range,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Vec<CodeAction> {
+    let text = rope.to_string();
+    let start = position_to_byte(rope, range.start);
+    let Some(selected) = text.get(start..position_to_byte(rope, range.end)) else {
+        return vec![];
+    };
+
+    // Whitespace around the selection is not part of the expression.
+    let start = start + selected.len() - selected.trim_start().len();
+    let expression = selected.trim();
+    if expression.is_empty() {
+        return vec![];
+    }
+    let range = Range::new(
+        byte_to_position(rope, start),
+        byte_to_position(rope, start + expression.len()),
+    );
+    if !is_expression(rope, &text, range) {
+        return vec![];
+    }
+
+    let Some(ty) = context::expression_type(&text, expression, range.start, functions, aliases)
+        .or_else(|| context::expected_type(&text, range.start, functions))
+        .filter(|ty| ty != "()")
+    else {
+        return vec![];
+    };
+    let bindings = scope::bindings_at(&text, range.start, aliases);
+    let tokens = lexer::significant(&lexer::tokenize(&text));
+
+    let mut actions = Vec::new();
+    if let Some(edits) = extract_let(&text, &tokens, range, start, expression, &ty, &bindings) {
+        actions.push(code_action(
+            "Extract into variable".to_string(),
+            CodeActionKind::REFACTOR_EXTRACT,
+            uri,
+            edits,
+        ));
+    }
+    if let Some(edits) = extract_function(&text, range, expression, &ty, &bindings, functions) {
+        actions.push(code_action(
+            "Extract into function".to_string(),
+            CodeActionKind::REFACTOR_EXTRACT,
+            uri,
+            edits,
+        ));
+    }
+    actions
+}
+
+/// Check if `range` is exactly an expression of a function in the program.
+fn is_expression(rope: &Rope, text: &str, range: Range) -> bool {
+    let Ok(program) = parse::Program::parse_from_str(text) else {
+        return false;
+    };
+    let (Ok(start), Ok(end)) = (
+        position_to_span(rope, range.start),
+        position_to_span(rope, range.end),
+    ) else {
+        return false;
+    };
+    let selection = Span {
+        start: start.start,
+        end: end.end,
+    };
+
+    program
+        .items()
+        .iter()
+        .filter_map(|item| match item {
+            parse::Item::Function(func) => Some(func),
+            _ => None,
+        })
+        .filter(|func| span_contains(func.span(), &selection))
+        .any(|func| {
+            parse::ExprTree::Expression(func.body())
+                .pre_order_iter()
+                .any(|expr| {
+                    let span: &Span = match expr {
+                        parse::ExprTree::Expression(expression) => expression.as_ref(),
+                        parse::ExprTree::Single(single) => single.as_ref(),
+                        _ => return false,
+                    };
+                    *span == selection
+                })
+        })
+}
+
+/// Return edits binding the expression to a new variable declared before the statement, which
+/// contains the expression starting at byte `start`.
+fn extract_let(
+    text: &str,
+    tokens: &[Token],
+    range: Range,
+    start: usize,
+    expression: &str,
+    ty: &str,
+    bindings: &[Binding],
+) -> Option<Vec<TextEdit>> {
+    let statement = statement_start(tokens, start)?;
+    let name = unique_name("value", |name| {
+        bindings.iter().any(|binding| binding.name == name)
+    });
+
+    let indent = indentation(text, statement.start.line);
+    let separator = if indent.chars().count() == statement.start.character as usize {
+        format!("\n{indent}")
+    } else {
+        " ".to_string()
+    };
+    let declaration = format!("let {name}: {ty} = {expression};{separator}");
+
+    if statement.start == range.start {
+        return Some(vec![TextEdit::new(range, format!("{declaration}{name}"))]);
+    }
+    Some(vec![
+        TextEdit::new(Range::new(statement.start, statement.start), declaration),
+        TextEdit::new(range, name),
+    ])
+}
+
+/// Return the first token of the statement containing byte `offset`, or `None` if it is in an
+/// arm of `match`, where a statement can't be inserted.
+fn statement_start<'a>(tokens: &[Token<'a>], offset: usize) -> Option<Token<'a>> {
+    let before = tokens
+        .iter()
+        .position(|token| token.offset >= offset)
+        .unwrap_or(tokens.len());
+
+    // Positive depth counts brackets closed after the token, negative ones enclose the offset.
+    let mut depth = 0isize;
+    for index in (0..before).rev() {
+        match tokens[index].text {
+            ")" | "]" | "}" => depth += 1,
+            "(" | "[" => depth -= 1,
+            "{" if depth > 0 => depth -= 1,
+            "{" | ";" if depth <= 0 => return tokens.get(index + 1).copied(),
+            "=>" if depth <= 0 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Return edits moving the expression into a new function defined before the enclosing one,
+/// which parameters are the variables used by the expression.
+fn extract_function(
+    text: &str,
+    range: Range,
+    expression: &str,
+    ty: &str,
+    bindings: &[Binding],
+    functions: &Functions,
+) -> Option<Vec<TextEdit>> {
+    let parameters = free_variables(expression, bindings);
+    if parameters.iter().any(|binding| binding.ty.is_empty()) {
+        return None;
+    }
+    let name = unique_name("extracted", |name| functions.get_func(name).is_some());
+
+    let signature: Vec<String> = parameters
+        .iter()
+        .map(|binding| format!("{}: {}", binding.name, binding.ty))
+        .collect();
+    let arguments: Vec<&str> = parameters
+        .iter()
+        .map(|binding| binding.name.as_str())
+        .collect();
+
+    // Continuation lines of the expression are indented relative to its first line.
+    let indent = indentation(text, range.start.line);
+    let body: Vec<String> = expression
+        .lines()
+        .map(|line| format!("    {}", line.strip_prefix(indent).unwrap_or(line)))
+        .collect();
+
+    let position = library::definition_position(text, range.start);
+    Some(vec![
+        TextEdit::new(
+            Range::new(position, position),
+            format!(
+                "fn {name}({}) -> {ty} {{\n{}\n}}\n\n",
+                signature.join(", "),
+                body.join("\n")
+            ),
+        ),
+        TextEdit::new(range, format!("{name}({})", arguments.join(", "))),
+    ])
+}
+
+/// Return variables and parameters bound outside of the expression, which it uses, in the order
+/// of their first use.
+fn free_variables<'a>(expression: &str, bindings: &'a [Binding]) -> Vec<&'a Binding> {
+    let tokens = lexer::significant(&lexer::tokenize(expression));
+
+    let mut variables: Vec<&Binding> = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let path = index
+            .checked_sub(1)
+            .is_some_and(|previous| tokens[previous].is("::"));
+        let call = tokens
+            .get(index + 1)
+            .is_some_and(|next| next.is("::") || next.is("("));
+        if token.kind != TokenKind::Ident || path || call {
+            continue;
+        }
+
+        if let Some(binding) = bindings.iter().find(|binding| {
+            binding.name == token.text
+                && matches!(binding.kind, BindingKind::Parameter | BindingKind::Variable)
+        }) {
+            if !variables
+                .iter()
+                .any(|variable| variable.name == binding.name)
+            {
+                variables.push(binding);
+            }
+        }
+    }
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use tower_lsp_server::lsp_types::Position;
+
+    #[test]
+    fn test_extract_actions() {
+        let text = "fn main() {
+    let a: u32 = 1;
+    let b: u32 = 2;
+    let (_, c): (bool, u32) = jet::add_32(a, b);
+}";
+        let uri = Uri::from_str("file:///test.simf").unwrap();
+        let range = Range::new(Position::new(3, 29), Position::new(3, 47));
+
+        let actions = extract_actions(
+            &uri,
+            &Rope::from_str(text),
+            range,
+            &Functions::new(),
+            &Aliases::new(),
+        );
+        let edits: Vec<Vec<String>> = actions
+            .into_iter()
+            .map(|action| {
+                let changes = action.edit.unwrap().changes.unwrap();
+                changes
+                    .into_values()
+                    .next()
+                    .unwrap()
+                    .into_iter()
+                    .map(|edit| edit.new_text)
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(
+            edits,
+            vec![
+                vec![
+                    "let value: (bool, u32) = jet::add_32(a, b);\n    ".to_string(),
+                    "value".to_string(),
+                ],
+                vec![
+                    "fn extracted(a: u32, b: u32) -> (bool, u32) {\n    jet::add_32(a, b)\n}\n\n"
+                        .to_string(),
+                    "extracted(a, b)".to_string(),
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statement_start() {
+        let text = "fn f() { let x: u8 = g(a, h(b)); match x { Left(y) => y, } }";
+        let tokens = lexer::significant(&lexer::tokenize(text));
+
+        let start = statement_start(&tokens, text.find("b)").unwrap());
+        assert_eq!(start.map(|token| token.text), Some("let"));
+        assert_eq!(statement_start(&tokens, text.rfind('y').unwrap()), None);
+    }
+}
@@ -0,0 +1,344 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range as ByteRange;
+
+use ropey::Rope;
+use simplicityhl::parse;
+use tower_lsp_server::lsp_types::{CodeAction, CodeActionKind, Position, Range, TextEdit, Uri};
+
+use crate::code_action::{code_action, indentation, unique_name};
+use crate::completion::library;
+use crate::function::Functions;
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope::{self, BindingKind};
+use crate::type_utils::Aliases;
+use crate::utils::{
+    byte_to_position, find_all_references, find_related_call, position_to_byte, position_to_span,
+    span_to_positions,
+};
+
+/// Get refactorings of the custom function call at `position`, which replace the call with the
+/// body of the function. If it is the only call, the definition can be removed as well.
+pub fn inline_actions(
+    uri: &Uri,
+    rope: &Rope,
+    position: Position,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Vec<CodeAction> {
+    let Ok(span) = position_to_span(rope, position) else {
+        return vec![];
+    };
+    let all = functions.functions();
+    let Ok(Some(call)) = find_related_call(&all, span) else {
+        return vec![];
+    };
+    let parse::CallName::Custom(name) = call.name() else {
+        return vec![];
+    };
+    let Some(function) = functions.get_func(name.as_inner()) else {
+        return vec![];
+    };
+    let (call_start, call_end) = span_to_positions(rope, call.span());
+    let (definition_start, definition_end) = span_to_positions(rope, function.as_ref());
+
+    let text = rope.to_string();
+    let call_bytes = position_to_byte(rope, call_start)..position_to_byte(rope, call_end);
+    let definition =
+        position_to_byte(rope, definition_start)..position_to_byte(rope, definition_end);
+    let Some(inlined) = inline_call(&text, call_bytes, definition.clone(), aliases) else {
+        return vec![];
+    };
+
+    let call_edit = TextEdit::new(Range::new(call_start, call_end), inlined);
+    let mut actions = vec![code_action(
+        format!("Inline `{name}`"),
+        CodeActionKind::REFACTOR_INLINE,
+        uri,
+        vec![call_edit.clone()],
+    )];
+
+    let callers = find_all_references(rope, &all, call.name()).len();
+    if callers == 1 {
+        actions.push(code_action(
+            format!("Inline `{name}` and remove its definition"),
+            CodeActionKind::REFACTOR_INLINE,
+            uri,
+            vec![
+                definition_removal(&text, rope, definition.start, definition.end),
+                call_edit,
+            ],
+        ));
+    }
+    actions
+}
+
+/// Return code replacing the call at byte range `call` with the body of the function defined at
+/// byte range `definition`.
+///
+/// Arguments, which are variables, constants or literals, are substituted for the parameters,
+/// other arguments are bound to the parameters by `let`. Bindings, which would capture variables
+/// of the arguments, are renamed.
+fn inline_call(
+    text: &str,
+    call: ByteRange<usize>,
+    definition: ByteRange<usize>,
+    aliases: &Aliases,
+) -> Option<String> {
+    let line = u32::try_from(text.get(..call.start)?.matches('\n').count()).ok()?;
+    let indent = indentation(text, line);
+    let call_text = text.get(call)?;
+    let call_tokens = lexer::significant(&lexer::tokenize(call_text));
+    let open = call_tokens.iter().position(|token| token.is("("))?;
+    let close = scope::matching_close(&call_tokens, open)?;
+    let arguments: Vec<&[Token]> = scope::split_at_commas(&call_tokens[open + 1..close]);
+
+    let definition_text = text.get(definition)?;
+    let tokens = lexer::significant(&lexer::tokenize(definition_text));
+    if !tokens.get(2)?.is("(") {
+        return None;
+    }
+    let params_close = scope::matching_close(&tokens, 2)?;
+    let body_open = tokens
+        .iter()
+        .skip(params_close)
+        .position(|token| token.is("{"))?
+        + params_close;
+    let body_close = scope::matching_close(&tokens, body_open)?;
+    let parameters: Vec<_> = scope::bindings_at(definition_text, tokens[body_open].end, aliases)
+        .into_iter()
+        .filter(|binding| binding.kind == BindingKind::Parameter)
+        .collect();
+    if parameters.len() != arguments.len() {
+        return None;
+    }
+
+    let body = &definition_text[tokens[body_open].end_offset()..tokens[body_close].offset];
+    let body_tokens = lexer::tokenize(body);
+    let declared = declared_names(&lexer::significant(&body_tokens));
+
+    let captured: HashSet<&str> = arguments
+        .iter()
+        .flat_map(|argument| argument.iter())
+        .filter(|token| token.kind == TokenKind::Ident)
+        .map(|token| token.text)
+        .collect();
+    let mut taken: HashSet<String> = body_tokens
+        .iter()
+        .chain(call_tokens.iter())
+        .filter(|token| token.kind == TokenKind::Ident)
+        .map(|token| token.text.to_string())
+        .chain(parameters.iter().map(|binding| binding.name.clone()))
+        .collect();
+    let mut fresh = |name: &str| {
+        let name = unique_name(name, |name| taken.contains(name));
+        taken.insert(name.clone());
+        name
+    };
+
+    let mut substitutions: HashMap<&str, String> = HashMap::new();
+    let mut statements = Vec::new();
+    for (parameter, argument) in parameters.iter().zip(&arguments) {
+        let (first, last) = (argument.first()?, argument.last()?);
+        let argument_text = &call_text[first.offset..last.end_offset()];
+
+        let simple = match *argument {
+            [token] => matches!(token.kind, TokenKind::Ident | TokenKind::Number),
+            [module, separator, _] => {
+                separator.is("::") && (module.is("witness") || module.is("param"))
+            }
+            _ => false,
+        };
+        if simple && !declared.contains(&parameter.name.as_str()) {
+            substitutions.insert(&parameter.name, argument_text.to_string());
+            continue;
+        }
+
+        let mut name = parameter.name.clone();
+        if captured.contains(name.as_str()) {
+            name = fresh(&parameter.name);
+            substitutions.insert(&parameter.name, name.clone());
+        }
+        statements.push(format!("let {name}: {} = {argument_text};", parameter.ty));
+    }
+    for name in declared {
+        if captured.contains(name) && !substitutions.contains_key(name) {
+            substitutions.insert(name, fresh(name));
+        }
+    }
+
+    let body = substitute(&body_tokens, &substitutions);
+    let body = body.trim();
+    if statements.is_empty() && !body.contains(';') {
+        return Some(if body.is_empty() { "()" } else { body }.to_string());
+    }
+
+    // Lines of the body keep their indentation relative to the first line.
+    let mut lines = body.lines();
+    statements.extend(lines.next().map(str::to_string));
+    let rest: Vec<&str> = lines.collect();
+    let common = rest
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or_default();
+    statements.extend(
+        rest.iter()
+            .map(|line| line.get(common..).unwrap_or_default().to_string()),
+    );
+
+    let statements: Vec<String> = statements
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{indent}    {line}")
+            }
+        })
+        .collect();
+    Some(format!("{{\n{}\n{indent}}}", statements.join("\n")))
+}
+
+/// Return names bound by `let` statements and `match` arms.
+fn declared_names<'a>(tokens: &[Token<'a>]) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    let mut pattern = false;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text {
+            "let" => pattern = true,
+            ":" if pattern => pattern = false,
+            // Binding of an arm, e.g. `Left(x: u8) =>`.
+            ":" if index >= 2 && tokens[index - 2].is("(") => {
+                let name = tokens[index - 1];
+                if name.kind == TokenKind::Ident {
+                    names.push(name.text);
+                }
+            }
+            "_" => {}
+            _ if pattern && token.kind == TokenKind::Ident => names.push(token.text),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Replace variables in `tokens` according to `substitutions`, keeping paths and called names.
+fn substitute(tokens: &[Token], substitutions: &HashMap<&str, String>) -> String {
+    let significant: Vec<usize> = (0..tokens.len())
+        .filter(|index| !tokens[*index].is_trivia())
+        .collect();
+
+    let mut result = String::new();
+    for (position, index) in significant.iter().enumerate() {
+        let token = tokens[*index];
+        let path = position
+            .checked_sub(1)
+            .is_some_and(|previous| tokens[significant[previous]].is("::"));
+        let call = significant
+            .get(position + 1)
+            .is_some_and(|next| tokens[*next].is("::") || tokens[*next].is("("));
+
+        // Copy whitespace and comments preceding the token.
+        let previous_end = position
+            .checked_sub(1)
+            .map_or(0, |previous| significant[previous] + 1);
+        for trivia in &tokens[previous_end..*index] {
+            result.push_str(trivia.text);
+        }
+
+        match substitutions.get(token.text) {
+            Some(replacement) if token.kind == TokenKind::Ident && !path && !call => {
+                result.push_str(replacement);
+            }
+            _ => result.push_str(token.text),
+        }
+    }
+    if let Some(last) = significant.last() {
+        for trivia in &tokens[last + 1..] {
+            result.push_str(trivia.text);
+        }
+    }
+    result
+}
+
+/// Return edit removing the definition between bytes `start` and `end` with its doc comments and
+/// the blank lines after it.
+fn definition_removal(text: &str, rope: &Rope, start: usize, end: usize) -> TextEdit {
+    let fn_start = byte_to_position(rope, start);
+    let start =
+        library::definition_position(text, Position::new(fn_start.line, fn_start.character + 1));
+
+    let rest = &text[end..];
+    let end = if rest.trim_start().is_empty() {
+        end
+    } else {
+        end + rest.len() - rest.trim_start().len()
+    };
+
+    TextEdit::new(
+        Range::new(start, byte_to_position(rope, end)),
+        String::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_call() {
+        let text = "fn add(a: u32, b: u32) -> u32 {
+    let (_, sum): (bool, u32) = jet::add_32(a, b);
+    sum
+}
+
+fn main() {
+    let sum: u32 = 1;
+    let x: u32 = add(sum, jet::num_outputs());
+}";
+        let call = text.find("add(sum").unwrap();
+        let call = call..call + "add(sum, jet::num_outputs())".len();
+        let definition = 0..text.find("\n\nfn main").unwrap();
+
+        assert_eq!(
+            inline_call(text, call, definition, &Aliases::new()).as_deref(),
+            Some(
+                "{
+        let b: u32 = jet::num_outputs();
+        let (_, sum_2): (bool, u32) = jet::add_32(sum, b);
+        sum_2
+    }"
+            )
+        );
+    }
+
+    #[test]
+    fn test_inline_expression() {
+        let text =
+            "fn is_after(height: Height) -> bool { jet::le_32(height, jet::tx_lock_height()) }
+fn main() { assert!(is_after(param::HEIGHT)); }";
+        let call = text.find("is_after(param").unwrap();
+        let call = call..call + "is_after(param::HEIGHT)".len();
+        let definition = 0..text.find('\n').unwrap();
+
+        assert_eq!(
+            inline_call(text, call, definition, &Aliases::new()).as_deref(),
+            Some("jet::le_32(param::HEIGHT, jet::tx_lock_height())")
+        );
+    }
+
+    #[test]
+    fn test_definition_removal() {
+        let text = "/// Doc\nfn f() {}\n\nfn main() { f() }";
+        let rope = Rope::from_str(text);
+        let start = text.find("fn f").unwrap();
+        let edit = definition_removal(text, &rope, start, start + "fn f() {}".len());
+
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(0, 0), Position::new(3, 0))
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{CodeAction, CodeActionKind, Position, Range, TextEdit, Uri};
+
+use crate::code_action::code_action;
+use crate::completion::context;
+use crate::function::Functions;
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope;
+use crate::type_utils::{self, Aliases};
+
+/// Integer literal with its digits, the most significant first.
+struct Literal {
+    base: u32,
+    digits: Vec<u8>,
+}
+
+impl Literal {
+    /// Parse decimal, `0x` hexadecimal or `0b` binary literal, which may contain `_`.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.replace('_', "");
+        let (base, digits) = if let Some(hex) = text.strip_prefix("0x") {
+            (16, hex)
+        } else if let Some(bin) = text.strip_prefix("0b") {
+            (2, bin)
+        } else {
+            (10, text.as_str())
+        };
+        let digits = digits
+            .chars()
+            .map(|ch| ch.to_digit(base).and_then(|digit| u8::try_from(digit).ok()))
+            .collect::<Option<Vec<u8>>>()?;
+
+        (!digits.is_empty()).then_some(Self { base, digits })
+    }
+
+    /// Return bit width fixed by the literal: hexadecimal and binary literals have a digit for
+    /// every four bits or every bit.
+    fn width(&self) -> Option<usize> {
+        match self.base {
+            16 => Some(self.digits.len() * 4),
+            2 => Some(self.digits.len()),
+            _ => None,
+        }
+    }
+
+    /// Return the value as a literal in `base`, padded to `width` bits if it is not decimal.
+    fn to_base(&self, base: u32, width: Option<usize>) -> Option<String> {
+        let digits: String = convert(&self.digits, self.base, base)
+            .iter()
+            .filter_map(|digit| char::from_digit(u32::from(*digit), base))
+            .collect();
+        let (prefix, bits_per_digit) = match base {
+            10 => {
+                return Some(if digits.is_empty() {
+                    "0".to_string()
+                } else {
+                    digits
+                });
+            }
+            16 => ("0x", 4),
+            _ => ("0b", 1),
+        };
+
+        let width = width?;
+        if width % bits_per_digit != 0 || digits.len() > width / bits_per_digit {
+            return None;
+        }
+        Some(format!(
+            "{prefix}{}{digits}",
+            "0".repeat(width / bits_per_digit - digits.len())
+        ))
+    }
+}
+
+/// Convert digits between bases, dropping leading zeros.
+fn convert(digits: &[u8], from: u32, to: u32) -> Vec<u8> {
+    let mut number: Vec<u32> = digits.iter().map(|digit| u32::from(*digit)).collect();
+    let mut result = Vec::new();
+
+    while number.iter().any(|digit| *digit != 0) {
+        let mut remainder = 0;
+        for digit in &mut number {
+            let value = remainder * from + *digit;
+            *digit = value / to;
+            remainder = value % to;
+        }
+        result.push(u8::try_from(remainder).unwrap_or_default());
+    }
+    result.reverse();
+    result
+}
+
+/// Get conversions of the literal at `position` between decimal, hexadecimal and binary, and
+/// between a hexadecimal literal and an array of bytes.
+pub fn literal_actions(
+    uri: &Uri,
+    rope: &Rope,
+    position: Position,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Vec<CodeAction> {
+    let text = rope.to_string();
+    let tokens = lexer::significant(&lexer::tokenize(&text));
+
+    let mut actions = Vec::new();
+    if let Some(index) = tokens.iter().position(|token| {
+        token.kind == TokenKind::Number && token.start <= position && position <= token.end
+    }) {
+        actions.extend(number_actions(&text, &tokens, index, functions, aliases));
+    }
+    actions.extend(byte_array_action(&text, &tokens, position, aliases));
+
+    actions
+        .into_iter()
+        .map(|(title, edits)| code_action(title, CodeActionKind::REFACTOR_REWRITE, uri, edits))
+        .collect()
+}
+
+/// Return conversions of the number literal at `tokens[index]`.
+fn number_actions(
+    text: &str,
+    tokens: &[Token],
+    index: usize,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Vec<(String, Vec<TextEdit>)> {
+    let token = tokens[index];
+    let Some(literal) = Literal::parse(token.text) else {
+        return vec![];
+    };
+    let range = Range::new(token.start, token.end);
+
+    let annotation = annotation(tokens, index, index);
+    let ty = annotation
+        .map(|(first, last)| text[first.offset..last.end_offset()].to_string())
+        .or_else(|| context::expected_type(text, token.start, functions))
+        .map(|ty| type_utils::resolve_alias(&ty, aliases));
+    let bits = ty
+        .as_deref()
+        .and_then(|ty| ty.strip_prefix('u'))
+        .and_then(|bits| bits.parse::<usize>().ok());
+    let width = literal.width().or(bits);
+
+    let mut actions = Vec::new();
+    // Byte arrays can only be written as hexadecimal literals.
+    if ty.is_none() || bits.is_some() {
+        for (base, name) in [(10, "decimal"), (16, "hex"), (2, "binary")] {
+            if base == literal.base {
+                continue;
+            }
+            if let Some(converted) = literal.to_base(base, width) {
+                actions.push((
+                    format!("Convert to {name}"),
+                    vec![TextEdit::new(range, converted)],
+                ));
+            }
+        }
+    }
+
+    // Hexadecimal literal of `uN` or `[u8; N / 8]` can be written as an array of bytes.
+    let bytes = literal.digits.len() / 2;
+    let array_type = format!("[u8; {bytes}]");
+    let fits_array = ty.as_deref().is_some_and(|ty| {
+        ty == format!("u{}", bytes * 8) || type_utils::types_match(ty, &array_type, aliases)
+    });
+    if literal.base == 16 && literal.digits.len() % 2 == 0 && bytes > 1 && fits_array {
+        let elements: Vec<String> = literal
+            .digits
+            .chunks(2)
+            .map(|pair| format!("0x{:x}{:x}", pair[0], pair[1]))
+            .collect();
+        let mut edits = vec![TextEdit::new(range, format!("[{}]", elements.join(", ")))];
+        if bits.is_some() {
+            let Some((first, last)) = annotation else {
+                return actions;
+            };
+            edits.push(TextEdit::new(
+                Range::new(first.start, last.end),
+                array_type.clone(),
+            ));
+        }
+        actions.push((format!("Convert to `{array_type}` array"), edits));
+    }
+
+    actions
+}
+
+/// Return conversion of the array of byte literals enclosing `position` into a hexadecimal
+/// literal. The `[u8; N]` annotation is replaced by `uN` if it exists.
+fn byte_array_action(
+    text: &str,
+    tokens: &[Token],
+    position: Position,
+    aliases: &Aliases,
+) -> Option<(String, Vec<TextEdit>)> {
+    let (open, close) = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.is("[") && token.start <= position)
+        .filter_map(|(open, _)| Some((open, scope::matching_close(tokens, open)?)))
+        .rfind(|(_, close)| position <= tokens[*close].end)?;
+
+    let mut hex = String::from("0x");
+    for element in scope::split_at_commas(&tokens[open + 1..close]) {
+        let [number] = element else {
+            return None;
+        };
+        let byte = Literal::parse(number.text)?.to_base(16, Some(8))?;
+        hex.push_str(&byte[2..]);
+    }
+    let bytes = (hex.len() - 2) / 2;
+    if bytes < 2 {
+        return None;
+    }
+
+    let range = Range::new(tokens[open].start, tokens[close].end);
+    let mut edits = vec![TextEdit::new(range, hex)];
+    let integer = format!("u{}", bytes * 8);
+
+    let annotation = annotation(tokens, open, close).filter(|(first, last)| {
+        let ty = &text[first.offset..last.end_offset()];
+        type_utils::types_match(ty, &format!("[u8; {bytes}]"), aliases)
+    });
+    let title = match annotation {
+        Some((first, last)) if type_utils::structure(&integer, aliases).is_some() => {
+            edits.push(TextEdit::new(
+                Range::new(first.start, last.end),
+                integer.clone(),
+            ));
+            format!("Convert to `{integer}` literal")
+        }
+        _ => "Convert to hex literal".to_string(),
+    };
+    Some((title, edits))
+}
+
+/// Return the first and the last token of the type annotation of `let` or `const`, which value
+/// are exactly `tokens[first..=last]`.
+fn annotation<'a>(
+    tokens: &[Token<'a>],
+    first: usize,
+    last: usize,
+) -> Option<(Token<'a>, Token<'a>)> {
+    let equals = first.checked_sub(1)?;
+    if !tokens[equals].is("=") || !tokens.get(last + 1)?.is(";") {
+        return None;
+    }
+    let declaration = tokens[..equals]
+        .iter()
+        .rposition(|token| token.is("let") || token.is("const"))?;
+    let statement = &tokens[declaration..equals];
+    if statement
+        .iter()
+        .any(|token| matches!(token.text, "=" | "{" | "}"))
+    {
+        return None;
+    }
+
+    let colon = statement.iter().position(|token| token.is(":"))?;
+    let ty = &statement[colon + 1..];
+    Some((*ty.first()?, *ty.last()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn actions(text: &str, position: Position) -> Vec<(String, Vec<String>)> {
+        let uri = Uri::from_str("file:///test.simf").unwrap();
+        literal_actions(
+            &uri,
+            &Rope::from_str(text),
+            position,
+            &Functions::new(),
+            &Aliases::new(),
+        )
+        .into_iter()
+        .map(|action| {
+            let changes = action.edit.unwrap().changes.unwrap();
+            let edits = changes.into_values().next().unwrap();
+            (
+                action.title,
+                edits.into_iter().map(|edit| edit.new_text).collect(),
+            )
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_convert_number() {
+        assert_eq!(
+            actions("fn main() { let x: u8 = 255; }", Position::new(0, 25)),
+            vec![
+                ("Convert to hex".to_string(), vec!["0xff".to_string()]),
+                (
+                    "Convert to binary".to_string(),
+                    vec!["0b11111111".to_string()]
+                ),
+            ]
+        );
+
+        let literal = Literal::parse("0x0_1_00").unwrap();
+        assert_eq!(literal.to_base(10, None).as_deref(), Some("256"));
+        assert_eq!(literal.to_base(2, Some(8)), None);
+    }
+
+    #[test]
+    fn test_convert_byte_array() {
+        assert_eq!(
+            actions(
+                "mod param { const KEY: u32 = 0x0102030a; }",
+                Position::new(0, 30)
+            ),
+            vec![
+                (
+                    "Convert to decimal".to_string(),
+                    vec!["16909066".to_string()]
+                ),
+                (
+                    "Convert to binary".to_string(),
+                    vec!["0b00000001000000100000001100001010".to_string()]
+                ),
+                (
+                    "Convert to `[u8; 4]` array".to_string(),
+                    vec![
+                        "[0x01, 0x02, 0x03, 0x0a]".to_string(),
+                        "[u8; 4]".to_string()
+                    ]
+                ),
+            ]
+        );
+
+        assert_eq!(
+            actions(
+                "mod param { const KEY: [u8; 4] = [0x01, 0x02, 3, 0x0a]; }",
+                Position::new(0, 33)
+            ),
+            vec![(
+                "Convert to `u32` literal".to_string(),
+                vec!["0x0102030a".to_string(), "u32".to_string()]
+            )]
+        );
+    }
+}
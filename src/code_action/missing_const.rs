@@ -0,0 +1,147 @@
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{CodeAction, CodeActionKind, Position, Range, TextEdit, Uri};
+
+use crate::code_action::code_action;
+use crate::completion::context;
+use crate::function::Functions;
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope;
+use crate::type_utils::{self, Aliases};
+
+/// Get quick fix declaring `witness::NAME` or `param::NAME` used at `position`, which is missing
+/// from `mod witness` or `mod param`. The type is expected by the usage, and the value is zero.
+pub fn missing_const_actions(
+    uri: &Uri,
+    rope: &Rope,
+    position: Position,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Vec<CodeAction> {
+    let text = rope.to_string();
+    let tokens = lexer::significant(&lexer::tokenize(&text));
+
+    let Some([module, _, name]) = tokens.windows(3).find(|window| {
+        matches!(window[0].text, "witness" | "param")
+            && window[1].is("::")
+            && window[2].kind == TokenKind::Ident
+            && window[0].start <= position
+            && position <= window[2].end
+    }) else {
+        return vec![];
+    };
+    if scope::module_constants(&text, module.text)
+        .iter()
+        .any(|binding| binding.name == name.text)
+    {
+        return vec![];
+    }
+
+    let Some(ty) = context::expected_type(&text, module.start, functions) else {
+        return vec![];
+    };
+    let Some(value) = type_utils::zero_value(&ty, aliases) else {
+        return vec![];
+    };
+    let declaration = format!("const {}: {ty} = {value};", name.text);
+
+    vec![code_action(
+        format!("Declare `{}::{}`", module.text, name.text),
+        CodeActionKind::QUICKFIX,
+        uri,
+        vec![declaration_edit(&tokens, module.text, &declaration)],
+    )]
+}
+
+/// Return edit adding the declaration at the end of `mod <module>`, or adding the module after
+/// the other modules at the top of the document.
+fn declaration_edit(tokens: &[Token], module: &str, declaration: &str) -> TextEdit {
+    // Modules with indices of their opening and closing braces.
+    let modules: Vec<(&str, usize, usize)> = tokens
+        .windows(3)
+        .enumerate()
+        .filter(|(_, window)| window[0].is("mod") && window[2].is("{"))
+        .filter_map(|(index, window)| {
+            Some((
+                window[1].text,
+                index + 2,
+                scope::matching_close(tokens, index + 2)?,
+            ))
+        })
+        .collect();
+
+    if let Some((_, open, close)) = modules.iter().find(|(name, ..)| *name == module) {
+        let (open, close) = (tokens[*open], tokens[*close]);
+        // The closing brace stays on its own line.
+        if close.start.line > open.end.line {
+            let start = Position::new(close.start.line, 0);
+            return TextEdit::new(Range::new(start, start), format!("    {declaration}\n"));
+        }
+        return TextEdit::new(
+            Range::new(open.end, close.start),
+            format!("\n    {declaration}\n"),
+        );
+    }
+
+    let module = format!("mod {module} {{\n    {declaration}\n}}");
+    match modules.last() {
+        Some((_, _, close)) => {
+            let end = tokens[*close].end;
+            TextEdit::new(Range::new(end, end), format!("\n\n{module}"))
+        }
+        None => TextEdit::new(Range::default(), format!("{module}\n\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::split_at_marker;
+    use std::str::FromStr;
+
+    /// Return edit of the action declaring the constant, which name starts at the `|` marker.
+    fn declaration_at_marker(text: &str) -> Option<TextEdit> {
+        let (text, position) = split_at_marker(text);
+        let rope = Rope::from_str(&text);
+        let uri = Uri::from_str("file:///test.simf").unwrap();
+
+        let mut actions =
+            missing_const_actions(&uri, &rope, position, &Functions::new(), &Aliases::new());
+        let action = actions.pop()?;
+        let changes = action.edit?.changes?;
+        changes.into_values().next()?.pop()
+    }
+
+    #[test]
+    fn test_missing_const_actions() {
+        let edit = declaration_at_marker("fn main() { let x: u32 = witness::|AMOUNT; }").unwrap();
+        assert_eq!(edit.range, Range::default());
+        assert_eq!(
+            edit.new_text,
+            "mod witness {\n    const AMOUNT: u32 = 0x00000000;\n}\n\n"
+        );
+
+        let edit = declaration_at_marker(
+            "mod witness {\n    const A: u8 = 0x00;\n}\n\nfn main() { let k: Pubkey = param::|KEY; }",
+        )
+        .unwrap();
+        assert_eq!(edit.range.start, Position::new(2, 1));
+        assert_eq!(
+            edit.new_text,
+            format!(
+                "\n\nmod param {{\n    const KEY: Pubkey = 0x{};\n}}",
+                "0".repeat(64)
+            )
+        );
+
+        let edit = declaration_at_marker(
+            "mod witness {\n    const A: u8 = 0x00;\n}\n\nfn main() { let b: u8 = witness::|B; }",
+        )
+        .unwrap();
+        assert_eq!(edit.range.start, Position::new(2, 0));
+        assert_eq!(edit.new_text, "    const B: u8 = 0x00;\n");
+
+        let declared =
+            "mod witness {\n    const A: u8 = 0x00;\n}\nfn main() { let a: u8 = witness::|A; }";
+        assert_eq!(declaration_at_marker(declared), None);
+    }
+}
@@ -0,0 +1,49 @@
+pub mod doc_stub;
+pub mod extract;
+pub mod inline;
+pub mod literal;
+pub mod missing_const;
+pub mod quick_fix;
+
+use std::collections::HashMap;
+
+use tower_lsp_server::lsp_types::{CodeAction, CodeActionKind, TextEdit, Uri, WorkspaceEdit};
+
+/// Create code action of `kind`, which applies `edits` to the document `uri`.
+pub fn code_action(
+    title: String,
+    kind: CodeActionKind,
+    uri: &Uri,
+    edits: Vec<TextEdit>,
+) -> CodeAction {
+    CodeAction {
+        title,
+        kind: Some(kind),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Return `base` or the first of `base_2`, `base_3`, ... which is not `taken`.
+pub fn unique_name(base: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(base) {
+        return base.to_string();
+    }
+    let mut index = 2;
+    loop {
+        let name = format!("{base}_{index}");
+        if !taken(&name) {
+            return name;
+        }
+        index += 1;
+    }
+}
+
+/// Return leading whitespace of the line.
+pub fn indentation(text: &str, line: u32) -> &str {
+    let line = text.lines().nth(line as usize).unwrap_or_default();
+    &line[..line.len() - line.trim_start().len()]
+}
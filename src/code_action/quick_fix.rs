@@ -0,0 +1,255 @@
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{
+    CodeAction, CodeActionKind, Diagnostic, Position, Range, TextEdit, Uri,
+};
+
+use crate::code_action::{code_action, indentation};
+use crate::lexer::{self, Token};
+use crate::scope;
+use crate::type_utils::{self, Aliases};
+use crate::utils::position_to_byte;
+
+/// Return expected and found types of the diagnostic
+/// "Expected expression of type `X`, found type `Y`".
+fn type_mismatch(message: &str) -> Option<(&str, &str)> {
+    let rest = message.strip_prefix("Expected expression of type `")?;
+    let (expected, rest) = rest.split_once("`, found type `")?;
+    let (found, _) = rest.split_once('`')?;
+    Some((expected, found))
+}
+
+/// Get quick fixes of the type mismatch diagnostic, which range is the mismatched expression:
+/// cast with `into`, `unwrap` of `Option`, missing tail expression and change of the return type.
+pub fn type_mismatch_fixes(
+    uri: &Uri,
+    rope: &Rope,
+    diagnostic: &Diagnostic,
+    aliases: &Aliases,
+) -> Vec<CodeAction> {
+    let Some((expected, found)) = type_mismatch(&diagnostic.message) else {
+        return vec![];
+    };
+    let text = rope.to_string();
+    let start = position_to_byte(rope, diagnostic.range.start);
+    let end = position_to_byte(rope, diagnostic.range.end);
+    let Some(expression) = text.get(start..end) else {
+        return vec![];
+    };
+    let tokens = lexer::significant(&lexer::tokenize(&text));
+
+    let mut fixes = Vec::new();
+    let replace = |new_text: String| vec![TextEdit::new(diagnostic.range, new_text)];
+
+    if !type_utils::types_match(expected, found, aliases)
+        && type_utils::is_castable(found, expected, aliases)
+    {
+        fixes.push((
+            format!("Cast to `{expected}` with `<{found}>::into`"),
+            replace(format!("<{found}>::into({expression})")),
+        ));
+    }
+
+    let resolved = type_utils::resolve_alias(found, aliases);
+    if let Some([element]) = type_utils::generic_arguments(&resolved, "Option").as_deref() {
+        if type_utils::types_match(element, expected, aliases) {
+            fixes.push((
+                "Unwrap `Option` with `unwrap`".to_string(),
+                replace(format!("unwrap({expression})")),
+            ));
+        }
+    }
+
+    if found == "()" {
+        if let Some((value, edit)) = tail_edit(&text, &tokens, end, expected, aliases) {
+            fixes.push((format!("Add tail expression `{value}`"), vec![edit]));
+        }
+    }
+
+    if let Some(edit) = return_type_edit(&tokens, end, found) {
+        fixes.push((format!("Change return type to `{found}`"), vec![edit]));
+    }
+
+    fixes
+        .into_iter()
+        .map(|(title, edits)| CodeAction {
+            diagnostics: Some(vec![diagnostic.clone()]),
+            ..code_action(title, CodeActionKind::QUICKFIX, uri, edits)
+        })
+        .collect()
+}
+
+/// Return index of the `}` closing the block, which ends at byte `end` or is the first token
+/// after it.
+fn closing_brace(tokens: &[Token], end: usize) -> Option<usize> {
+    let index = tokens
+        .iter()
+        .position(|token| token.end_offset() == end)
+        .filter(|index| tokens[*index].is("}"))
+        .or_else(|| tokens.iter().position(|token| token.offset >= end))?;
+    tokens[index].is("}").then_some(index)
+}
+
+/// Return tail expression of type `ty` for the block ending at byte `end` with its edit: the
+/// latest variable of the type or its zero value.
+fn tail_edit(
+    text: &str,
+    tokens: &[Token],
+    end: usize,
+    ty: &str,
+    aliases: &Aliases,
+) -> Option<(String, TextEdit)> {
+    let close = tokens
+        .iter()
+        .position(|token| token.end_offset() == end)
+        .filter(|index| tokens[*index].is("}"))?;
+    let previous = tokens.get(close.checked_sub(1)?)?;
+    if !previous.is(";") && !previous.is("{") {
+        return None;
+    }
+    let body = body_indentation(text, tokens, close);
+    let close = tokens[close];
+
+    let value = scope::bindings_at(text, close.start, aliases)
+        .into_iter()
+        .rev()
+        .find(|binding| type_utils::types_match(&binding.ty, ty, aliases))
+        .map(|binding| binding.name)
+        .or_else(|| type_utils::zero_value(ty, aliases))?;
+
+    let edit = match body {
+        Some(indent) if previous.end.line < close.start.line => {
+            let start = Position::new(close.start.line, 0);
+            TextEdit::new(Range::new(start, start), format!("{indent}{value}\n"))
+        }
+        _ => TextEdit::new(Range::new(close.start, close.start), format!("{value} ")),
+    };
+    Some((value, edit))
+}
+
+/// Return indentation of the lines of the block closed by token `close`: that of its first
+/// statement, or for an empty block the indentation of `}` followed by the first indentation in
+/// the document.
+fn body_indentation(text: &str, tokens: &[Token], close: usize) -> Option<String> {
+    let open = scope::matching_open(tokens, close)?;
+    let first = tokens[open + 1];
+    if open + 1 < close && first.start.line > tokens[open].start.line {
+        return Some(indentation(text, first.start.line).to_string());
+    }
+    let level = (0..)
+        .zip(text.lines())
+        .map(|(line, _)| indentation(text, line))
+        .find(|indent| !indent.is_empty())?;
+    let indent = indentation(text, tokens[close].start.line);
+    Some(format!("{indent}{level}"))
+}
+
+/// Return edit changing the return type of the function to `ty`, if the function body or its
+/// tail expression ends at byte `end`.
+fn return_type_edit(tokens: &[Token], end: usize, ty: &str) -> Option<TextEdit> {
+    let close = closing_brace(tokens, end)?;
+    let open = scope::matching_open(tokens, close)?;
+    let function = tokens[..open].iter().rposition(|token| token.is("fn"))?;
+    if !tokens.get(function + 2)?.is("(") {
+        return None;
+    }
+    let params_close = scope::matching_close(tokens, function + 2)?;
+    let params_end = tokens[params_close].end;
+
+    match tokens.get(params_close + 1..open)? {
+        [] if ty != "()" => Some(TextEdit::new(
+            Range::new(params_end, params_end),
+            format!(" -> {ty}"),
+        )),
+        [arrow, ret @ ..] if arrow.is("->") && !ret.is_empty() => {
+            if ty == "()" {
+                return Some(TextEdit::new(
+                    Range::new(params_end, tokens[open].start),
+                    " ".to_string(),
+                ));
+            }
+            let (first, last) = (ret.first()?, ret.last()?);
+            Some(TextEdit::new(
+                Range::new(first.start, last.end),
+                ty.to_string(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn fixes(text: &str, range: Range, expected: &str, found: &str) -> Vec<(String, String)> {
+        let uri = Uri::from_str("file:///test.simf").unwrap();
+        let diagnostic = Diagnostic::new_simple(
+            range,
+            format!("Expected expression of type `{expected}`, found type `{found}`"),
+        );
+        type_mismatch_fixes(&uri, &Rope::from_str(text), &diagnostic, &Aliases::new())
+            .into_iter()
+            .map(|action| {
+                let changes = action.edit.unwrap().changes.unwrap();
+                let edit = changes.into_values().next().unwrap().remove(0);
+                (action.title, edit.new_text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cast_and_unwrap() {
+        let text = "fn f(x: u8, y: Option<u8>) -> (u4, u4) { x }";
+        let range = Range::new(Position::new(0, 41), Position::new(0, 42));
+        assert_eq!(
+            fixes(text, range, "(u4, u4)", "u8"),
+            vec![
+                (
+                    "Cast to `(u4, u4)` with `<u8>::into`".to_string(),
+                    "<u8>::into(x)".to_string()
+                ),
+                ("Change return type to `u8`".to_string(), "u8".to_string()),
+            ]
+        );
+
+        let text = "fn f(y: Option<u8>) { let x: u8 = y; }";
+        let range = Range::new(Position::new(0, 34), Position::new(0, 35));
+        assert_eq!(
+            fixes(text, range, "u8", "Option<u8>"),
+            vec![(
+                "Unwrap `Option` with `unwrap`".to_string(),
+                "unwrap(y)".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_missing_tail_expression() {
+        let text = "fn f(a: u32) -> u32 {\n    let b: u32 = a;\n}";
+        let range = Range::new(Position::new(0, 20), Position::new(2, 1));
+        assert_eq!(
+            fixes(text, range, "u32", "()"),
+            vec![
+                ("Add tail expression `b`".to_string(), "    b\n".to_string()),
+                ("Change return type to `()`".to_string(), " ".to_string()),
+            ]
+        );
+
+        let text = "fn f(a: u32) -> u32 {\n\tlet b: u32 = a;\n}";
+        assert_eq!(
+            fixes(text, range, "u32", "()")[0],
+            ("Add tail expression `b`".to_string(), "\tb\n".to_string())
+        );
+
+        let text = "fn g() {\n  let c: u8 = 1;\n}\n\nfn f() -> u8 {\n}";
+        let range = Range::new(Position::new(4, 13), Position::new(5, 1));
+        assert_eq!(
+            fixes(text, range, "u8", "()")[0],
+            (
+                "Add tail expression `0x00`".to_string(),
+                "  0x00\n".to_string()
+            )
+        );
+    }
+}
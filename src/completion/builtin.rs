@@ -8,43 +8,63 @@ use simplicityhl::{
 };
 
 use crate::completion::types::FunctionTemplate;
+use crate::function::Functions;
+use crate::type_utils::generic_arguments;
 
-/// Get completion of builtin functions. They are all defined in [`simplicityhl::parse::CallName`]
-pub fn get_builtin_functions() -> Vec<FunctionTemplate> {
-    let ty = AliasedType::from(AliasName::from_str_unchecked("T"));
+/// Types known at the call site of a builtin function, which replace type placeholders.
+#[derive(Debug, Default)]
+pub struct CallSite {
+    /// Type of the first argument.
+    pub argument: Option<String>,
+    /// Type expected of the result, e.g. from the `let` annotation.
+    pub expected: Option<String>,
+}
+
+impl CallSite {
+    /// Return generic argument at `index` of the argument type `name`, e.g. `u8` of `Option<u8>`.
+    fn argument_generic(&self, name: &str, index: usize) -> Option<String> {
+        generic_arguments(self.argument.as_deref()?, name)?
+            .into_iter()
+            .nth(index)
+    }
+}
+
+/// Get builtin functions with placeholders instead of generic arguments and bound functions. They
+/// are all defined in [`simplicityhl::parse::CallName`]
+pub fn get_builtin_calls() -> Vec<CallName> {
+    let ty = |name| AliasedType::from(AliasName::from_str_unchecked(name));
     let function_name = FunctionName::from_str_unchecked("fn");
     let Some(some) = NonZero::new(1) else {
         return vec![];
     };
 
-    let functions = vec![
-        CallName::UnwrapLeft(ty.clone()),
-        CallName::UnwrapRight(ty.clone()),
+    vec![
+        CallName::UnwrapLeft(ty("R")),
+        CallName::UnwrapRight(ty("L")),
         CallName::Unwrap,
-        CallName::IsNone(ty.clone()),
+        CallName::IsNone(ty("T")),
         CallName::Assert,
         CallName::Debug,
         CallName::Panic,
         CallName::Fold(function_name.clone(), NonZeroPow2Usize::TWO),
         CallName::ArrayFold(function_name.clone(), some),
         CallName::ForWhile(function_name.clone()),
-        CallName::TypeCast(ty.clone()),
-    ];
-
-    functions.iter().filter_map(match_callname).collect()
+        CallName::TypeCast(ty("Input")),
+    ]
 }
 
 /// Match [`simplicityhl::parse::CallName`] and return [`FunctionTemplate`]
 pub fn match_callname(call: &CallName) -> Option<FunctionTemplate> {
     let doc = builtin_documentation(call);
     match call {
+        // The generic argument of `unwrap_left` is the right type, the left type is inferred.
         CallName::UnwrapLeft(aliased_type) => {
             let ty = aliased_type.to_string();
             Some(FunctionTemplate::new(
                 "unwrap_left",
                 vec![format!("{ty}")],
-                vec![format!("Either<{ty}, U>")],
-                ty,
+                vec![format!("Either<L, {ty}>")],
+                "L",
                 doc,
             ))
         }
@@ -53,8 +73,8 @@ pub fn match_callname(call: &CallName) -> Option<FunctionTemplate> {
             Some(FunctionTemplate::new(
                 "unwrap_right",
                 vec![format!("{ty}")],
-                vec![format!("Either<T, {ty}>")],
-                ty,
+                vec![format!("Either<{ty}, R>")],
+                "R",
                 doc,
             ))
         }
@@ -129,22 +149,167 @@ pub fn match_callname(call: &CallName) -> Option<FunctionTemplate> {
     }
 }
 
+/// Match [`simplicityhl::parse::CallName`] at the call site and return [`FunctionTemplate`] with
+/// concrete types, bound function signature and list size instead of placeholders.
+///
+/// Types, which are not written in the call, are taken from `site`. Falls back to
+/// [`match_callname`] when the bound function is not found in `functions`.
+pub fn instantiate_callname(
+    call: &CallName,
+    functions: &Functions,
+    site: &CallSite,
+) -> Option<FunctionTemplate> {
+    let doc = builtin_documentation(call);
+    match call {
+        CallName::UnwrapLeft(right) => {
+            let left = site
+                .argument_generic("Either", 0)
+                .or_else(|| site.expected.clone())
+                .unwrap_or_else(|| "L".to_string());
+            Some(FunctionTemplate::new(
+                "unwrap_left",
+                vec![right.to_string()],
+                vec![format!("Either<{left}, {right}>")],
+                left,
+                doc,
+            ))
+        }
+        CallName::UnwrapRight(left) => {
+            let right = site
+                .argument_generic("Either", 1)
+                .or_else(|| site.expected.clone())
+                .unwrap_or_else(|| "R".to_string());
+            Some(FunctionTemplate::new(
+                "unwrap_right",
+                vec![left.to_string()],
+                vec![format!("Either<{left}, {right}>")],
+                right,
+                doc,
+            ))
+        }
+        CallName::Unwrap => {
+            let inner = site
+                .argument_generic("Option", 0)
+                .or_else(|| site.expected.clone())
+                .unwrap_or_else(|| "T".to_string());
+            Some(FunctionTemplate::simple(
+                "unwrap",
+                vec![format!("Option<{inner}>")],
+                inner,
+                doc,
+            ))
+        }
+        CallName::IsNone(some) => Some(FunctionTemplate::new(
+            "is_none",
+            vec![some.to_string()],
+            vec![format!("Option<{some}>")],
+            "bool",
+            doc,
+        )),
+        CallName::Debug => {
+            let ty = site
+                .argument
+                .clone()
+                .or_else(|| site.expected.clone())
+                .unwrap_or_else(|| "T".to_string());
+            Some(FunctionTemplate::simple("dbg!", vec![ty.clone()], ty, doc))
+        }
+        CallName::Fold(name, size) => {
+            let Some((element, accumulator, ret)) = fold_step_types(name, functions) else {
+                return match_callname(call);
+            };
+            Some(FunctionTemplate::new(
+                "fold",
+                vec![name.to_string(), size.to_string()],
+                vec![
+                    format!("list: List<{element}, {size}>"),
+                    format!("initial_accumulator: {accumulator}"),
+                ],
+                ret,
+                doc,
+            ))
+        }
+        CallName::ArrayFold(name, size) => {
+            let Some((element, accumulator, ret)) = fold_step_types(name, functions) else {
+                return match_callname(call);
+            };
+            Some(FunctionTemplate::new(
+                "array_fold",
+                vec![name.to_string(), size.to_string()],
+                vec![
+                    format!("array: [{element}; {size}]"),
+                    format!("initial_accumulator: {accumulator}"),
+                ],
+                ret,
+                doc,
+            ))
+        }
+        CallName::ForWhile(name) => {
+            let Some(func) = functions.get_func(name.as_inner()) else {
+                return match_callname(call);
+            };
+            let [accumulator, context, _] = func.params() else {
+                return match_callname(call);
+            };
+            Some(FunctionTemplate::new(
+                "for_while",
+                vec![name.to_string()],
+                vec![
+                    format!("accumulator: {}", accumulator.ty()),
+                    format!("context: {}", context.ty()),
+                ],
+                func.ret()
+                    .map_or_else(|| "()".to_string(), ToString::to_string),
+                doc,
+            ))
+        }
+        CallName::TypeCast(aliased_type) => {
+            let ty = aliased_type.to_string();
+            Some(FunctionTemplate {
+                display_name: "into".into(),
+                generics: vec![ty.clone()],
+                args: vec![format!("input: {ty}")],
+                return_type: site.expected.clone().unwrap_or_else(|| "Output".into()),
+                description: doc,
+                snippet: format!("<{ty}>::into"),
+            })
+        }
+        _ => match_callname(call),
+    }
+}
+
+/// Return element, accumulator and return types of the function bound to `fold` or `array_fold`.
+fn fold_step_types(name: &FunctionName, functions: &Functions) -> Option<(String, String, String)> {
+    let func = functions.get_func(name.as_inner())?;
+    let [element, accumulator] = func.params() else {
+        return None;
+    };
+    Some((
+        element.ty().to_string(),
+        accumulator.ty().to_string(),
+        func.ret()
+            .map_or_else(|| "()".to_string(), ToString::to_string),
+    ))
+}
+
 /// Return documentation for builtin function.
 fn builtin_documentation(call: &CallName) -> String {
     String::from(match call {
         CallName::UnwrapLeft(_) =>
     "Extracts the left variant of an `Either` value.\n
-Returns the left-side value if it exists, otherwise panics.\n
+Returns the left-side value if it exists, otherwise panics. The generic argument is the right type, \
+the left type is inferred from the context.\n
 ```simplicityhl
-let x: Either<u8, u8> = Left(42);
-let y: u8 = unwrap_left::<u8>(x); // 42
+let x: Either<u8, u16> = Left(42);
+let y: u8 = unwrap_left::<u16>(x); // 42
 ```",
         CallName::UnwrapRight(_) =>
     "Extracts the right variant of an `Either` value.\n
-Returns the right-side value if it exists, otherwise panics.\n
+Returns the right-side value if it exists, otherwise panics. The generic argument is the left type, \
+the right type is inferred from the context.\n
 ```simplicityhl
-let x: Either<u8, u8> = Right(128);
-let y: u8 = unwrap_right::<u8>(x); // 128
+let x: Either<u8, u16> = Right(128);
+let y: u16 = unwrap_right::<u8>(x); // 128
 ```",
         CallName::Unwrap =>
     "Unwraps an `Option` value, panicking if it is `None`.\n
@@ -281,3 +446,69 @@ The above expression casts the value `input` of type `Input` into some output ty
 The input type of the cast is explicit while the output type is implicit.
 "
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::functions_from;
+
+    #[test]
+    fn test_instantiate_fold() {
+        let functions = functions_from(
+            "fn sum(elt: u32, acc: u64) -> u64 { acc }
+             fn main() {}",
+        );
+        let call = CallName::Fold(
+            FunctionName::from_str_unchecked("sum"),
+            NonZeroPow2Usize::TWO,
+        );
+
+        let template = instantiate_callname(&call, &functions, &CallSite::default())
+            .expect("Expected template");
+        assert_eq!(template.get_generic_name(), "fold::<sum, 2>");
+        assert_eq!(
+            template.args,
+            vec!["list: List<u32, 2>", "initial_accumulator: u64"]
+        );
+        assert_eq!(template.return_type, "u64");
+    }
+
+    #[test]
+    fn test_instantiate_type_cast() {
+        let call = CallName::TypeCast(AliasedType::from(AliasName::from_str_unchecked("u8")));
+        let site = CallSite {
+            argument: None,
+            expected: Some("(u4, u4)".to_string()),
+        };
+
+        let template =
+            instantiate_callname(&call, &Functions::new(), &site).expect("Expected template");
+        assert_eq!(template.get_generic_name(), "<u8>::into");
+        assert_eq!(template.args, vec!["input: u8"]);
+        assert_eq!(template.return_type, "(u4, u4)");
+    }
+
+    #[test]
+    fn test_instantiate_unwrap() {
+        let u16 = AliasedType::from(AliasName::from_str_unchecked("u16"));
+        let site = CallSite {
+            argument: Some("Either<u8, u16>".to_string()),
+            expected: None,
+        };
+
+        let template = instantiate_callname(&CallName::UnwrapLeft(u16), &Functions::new(), &site)
+            .expect("Expected template");
+        assert_eq!(template.get_generic_name(), "unwrap_left::<u16>");
+        assert_eq!(template.args, vec!["Either<u8, u16>"]);
+        assert_eq!(template.return_type, "u8");
+
+        let site = CallSite {
+            argument: None,
+            expected: Some("u32".to_string()),
+        };
+        let template = instantiate_callname(&CallName::Unwrap, &Functions::new(), &site)
+            .expect("Expected template");
+        assert_eq!(template.args, vec!["Option<u32>"]);
+        assert_eq!(template.return_type, "u32");
+    }
+}
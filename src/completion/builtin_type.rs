@@ -0,0 +1,70 @@
+use tower_lsp_server::lsp_types::{CompletionItem, CompletionItemKind, InsertTextFormat};
+
+use crate::type_utils::{Aliases, BUILTIN_ALIASES};
+
+/// Get completion of builtin types, with snippet placeholders for generic arguments.
+pub fn get_builtin_types() -> Vec<CompletionItem> {
+    let unsigned = ["u1", "u2", "u4", "u8", "u16", "u32", "u64", "u128", "u256"]
+        .into_iter()
+        .map(|ty| (ty, ty, "Unsigned integer"));
+
+    let generic = [
+        ("bool", "bool", "Boolean, which is `true` or `false`"),
+        ("()", "()", "Unit type"),
+        ("(A, B)", "(${1:A}, ${2:B})", "Tuple"),
+        ("[T; N]", "[${1:T}; ${2:N}]", "Array of fixed size `N`"),
+        (
+            "Either<L, R>",
+            "Either<${1:L}, ${2:R}>",
+            "Left value `L` or right value `R`",
+        ),
+        ("Option<T>", "Option<${1:T}>", "Some value `T` or none"),
+        (
+            "List<T, N>",
+            "List<${1:T}, ${2:N}>",
+            "List of fewer than `N` elements, where `N` is a power of two",
+        ),
+    ];
+
+    unsigned
+        .chain(generic)
+        .map(|(label, snippet, detail)| type_to_completion(label, snippet, detail))
+        .chain(
+            BUILTIN_ALIASES
+                .iter()
+                .map(|(alias, definition)| alias_to_completion(alias, definition)),
+        )
+        .collect()
+}
+
+/// Get completion of type aliases declared in the document.
+pub fn get_alias_completions(aliases: &Aliases) -> Vec<CompletionItem> {
+    aliases
+        .iter()
+        .map(|(alias, definition)| alias_to_completion(alias, definition))
+        .collect()
+}
+
+/// Convert builtin type to [`CompletionItem`].
+fn type_to_completion(label: &str, snippet: &str, detail: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::STRUCT),
+        detail: Some(detail.to_string()),
+        insert_text: Some(snippet.to_string()),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }
+}
+
+/// Convert type alias to [`CompletionItem`], showing its definition in `detail`.
+fn alias_to_completion(alias: &str, definition: &str) -> CompletionItem {
+    CompletionItem {
+        label: alias.to_string(),
+        kind: Some(CompletionItemKind::STRUCT),
+        detail: Some(format!("type {alias} = {definition}")),
+        insert_text: Some(alias.to_string()),
+        insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+        ..Default::default()
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+
+use simplicityhl::parse::Function;
+use tower_lsp_server::lsp_types::{CompletionItem, CompletionItemKind, InsertTextFormat};
+
+use crate::completion::context::Combinator;
+use crate::completion::function_to_template;
+use crate::scope::Binding;
+use crate::type_utils::{self, Aliases};
+
+/// Counter types accepted by the loop body of `for_while`.
+const COUNTER_TYPES: [&str; 5] = ["u1", "u2", "u4", "u8", "u16"];
+
+/// Get functions of the document, which can be passed to the combinator: `fn(E, A) -> A` for
+/// `fold` and `array_fold`, and `fn(A, C, uN) -> Either<B, A>` for `for_while`.
+pub fn get_function_completions(
+    combinator: Combinator,
+    functions: &[(&Function, &str)],
+    aliases: &Aliases,
+) -> Vec<CompletionItem> {
+    functions
+        .iter()
+        .filter(|(func, _)| fits(combinator, func, aliases))
+        .map(|(func, doc)| {
+            let template = function_to_template(func, doc);
+            CompletionItem {
+                label: template.display_name.clone(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some(template.get_signature()),
+                insert_text: Some(template.display_name),
+                insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Check if the signature of the function fits the combinator.
+fn fits(combinator: Combinator, func: &Function, aliases: &Aliases) -> bool {
+    let params: Vec<String> = func
+        .params()
+        .iter()
+        .map(|param| param.ty().to_string())
+        .collect();
+    let ret = func
+        .ret()
+        .map_or_else(|| "()".to_string(), ToString::to_string);
+
+    match (combinator, params.as_slice()) {
+        (Combinator::Fold | Combinator::ArrayFold, [_, accumulator]) => {
+            type_utils::types_match(accumulator, &ret, aliases)
+        }
+        (Combinator::ForWhile, [accumulator, _, counter]) => {
+            let counter = type_utils::resolve_alias(counter, aliases);
+            let ret = type_utils::resolve_alias(&ret, aliases);
+            COUNTER_TYPES.contains(&counter.as_str())
+                && type_utils::generic_arguments(&ret, "Either").is_some_and(|arguments| {
+                    match arguments.as_slice() {
+                        [_, next] => type_utils::types_match(next, accumulator, aliases),
+                        _ => false,
+                    }
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Get valid sizes `N` of the combinator: powers of two for `fold` and positive sizes for
+/// `array_fold`. Sizes of lists and arrays visible at the cursor are ranked first.
+pub fn get_size_completions(
+    combinator: Combinator,
+    bindings: &[Binding],
+    aliases: &Aliases,
+) -> Vec<CompletionItem> {
+    let (candidates, in_scope): (Vec<usize>, BTreeSet<usize>) = match combinator {
+        Combinator::Fold => (
+            (1..=16).map(|exponent| 1 << exponent).collect(),
+            bindings
+                .iter()
+                .filter_map(|binding| {
+                    let ty = type_utils::resolve_alias(&binding.ty, aliases);
+                    let arguments = type_utils::generic_arguments(&ty, "List")?;
+                    arguments.get(1)?.parse().ok()
+                })
+                .filter(|size: &usize| size.is_power_of_two() && *size > 1)
+                .collect(),
+        ),
+        Combinator::ArrayFold => (
+            (1..=16).collect(),
+            bindings
+                .iter()
+                .filter_map(|binding| {
+                    let ty = type_utils::resolve_alias(&binding.ty, aliases);
+                    type_utils::array_element(&ty)?.1.parse().ok()
+                })
+                .filter(|size: &usize| *size > 0)
+                .collect(),
+        ),
+        Combinator::ForWhile => return vec![],
+    };
+
+    in_scope
+        .iter()
+        .chain(candidates.iter().filter(|size| !in_scope.contains(size)))
+        .enumerate()
+        .map(|(index, size)| CompletionItem {
+            label: size.to_string(),
+            kind: Some(CompletionItemKind::VALUE),
+            detail: Some(match combinator {
+                Combinator::Fold => format!("List<E, {size}>"),
+                _ => format!("[E; {size}]"),
+            }),
+            // Sizes are listed in their order, which `sortText` keeps on the client.
+            sort_text: Some(format!("{index:04}")),
+            insert_text: Some(size.to_string()),
+            insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scope::BindingKind;
+    use simplicityhl::parse::{self, ParseFromStr};
+    use tower_lsp_server::lsp_types::Position;
+
+    #[test]
+    fn test_function_completions() {
+        let program = parse::Program::parse_from_str(
+            "fn sum(elt: u32, acc: u32) -> u32 { acc }
+             fn step(acc: u8, ctx: (), i: u4) -> Either<bool, u8> { Right(acc) }
+             fn main() {}",
+        )
+        .expect("Expected valid program");
+        let functions: Vec<(&Function, &str)> = program
+            .items()
+            .iter()
+            .filter_map(|item| match item {
+                parse::Item::Function(func) => Some((func, "")),
+                _ => None,
+            })
+            .collect();
+
+        let labels = |combinator| -> Vec<String> {
+            get_function_completions(combinator, &functions, &Aliases::new())
+                .into_iter()
+                .map(|item| item.label)
+                .collect()
+        };
+        assert_eq!(labels(Combinator::Fold), vec!["sum"]);
+        assert_eq!(labels(Combinator::ArrayFold), vec!["sum"]);
+        assert_eq!(labels(Combinator::ForWhile), vec!["step"]);
+    }
+
+    #[test]
+    fn test_size_completions() {
+        let bindings = [Binding {
+            name: "xs".to_string(),
+            ty: "List<u32, 8>".to_string(),
+            kind: BindingKind::Variable,
+            position: Position::new(0, 0),
+        }];
+
+        let sizes = get_size_completions(Combinator::Fold, &bindings, &Aliases::new());
+        assert_eq!(sizes[0].label, "8");
+        assert_eq!(sizes[1].label, "2");
+        assert!(
+            sizes
+                .windows(2)
+                .all(|pair| pair[0].sort_text < pair[1].sort_text)
+        );
+        assert!(sizes.iter().all(|item| {
+            item.label
+                .parse::<usize>()
+                .is_ok_and(usize::is_power_of_two)
+        }));
+
+        let sizes = get_size_completions(Combinator::ArrayFold, &bindings, &Aliases::new());
+        assert_eq!(sizes[0].label, "1");
+        assert!(get_size_completions(Combinator::ForWhile, &bindings, &Aliases::new()).is_empty());
+    }
+}
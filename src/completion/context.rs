@@ -0,0 +1,559 @@
+use std::str::FromStr;
+
+use simplicityhl::jet;
+use simplicityhl::simplicity::jet::Elements;
+use tower_lsp_server::lsp_types::Position;
+
+use crate::function::Functions;
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope;
+use crate::type_utils::{Aliases, types_match};
+
+/// Type expected at the cursor, together with aliases needed to compare types.
+#[derive(Debug)]
+pub struct ExpectedType<'a> {
+    pub ty: String,
+    pub aliases: &'a Aliases,
+}
+
+impl ExpectedType<'_> {
+    /// Check if value of type `ty` can be used at the cursor.
+    pub fn fits(&self, ty: &str) -> bool {
+        types_match(&self.ty, ty, self.aliases)
+    }
+}
+
+/// Syntactic construct around the cursor, used to offer only snippets valid there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxContext {
+    /// Between top-level items.
+    TopLevel,
+    /// Between constants of `mod witness` or `mod param`.
+    Module,
+    /// At the start of a statement inside a block.
+    Statement,
+    /// Where an expression is expected, e.g. after `=` or inside call arguments.
+    Expression,
+    /// Anywhere else, e.g. inside a pattern or a function signature.
+    Other,
+}
+
+/// `match` expression, which arm starts at the cursor.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MatchArms {
+    /// Source text of the matched expression.
+    pub scrutinee: String,
+    /// Position of the `match` keyword, where bindings used by the scrutinee are visible.
+    pub position: Position,
+    /// Constructors of the arms already written, e.g. `Left`.
+    pub written: Vec<String>,
+}
+
+/// Builtin function, which takes a function as its first generic argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// `fold::<f, N>` over a list of bounded length.
+    Fold,
+    /// `array_fold::<f, N>` over an array.
+    ArrayFold,
+    /// `for_while::<f>` with a bounded counter.
+    ForWhile,
+}
+
+impl Combinator {
+    /// Return combinator by its name, e.g. `fold`.
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fold" => Some(Self::Fold),
+            "array_fold" => Some(Self::ArrayFold),
+            "for_while" => Some(Self::ForWhile),
+            _ => None,
+        }
+    }
+}
+
+/// Find type expected at the cursor from the `let` annotation, parameter of the called function
+/// or return type of the enclosing function.
+pub fn expected_type(text: &str, position: Position, functions: &Functions) -> Option<String> {
+    let tokens = lexer::tokenize(text);
+    let before = tokens_before(&tokens, position);
+    expected_type_of(text, &before, functions)
+}
+
+/// Find type of the expression written at `position`: a variable, parameter, witness or
+/// parameter constant, or the result of a function or jet call.
+pub fn expression_type(
+    text: &str,
+    expression: &str,
+    position: Position,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Option<String> {
+    let tokens = lexer::significant(&lexer::tokenize(expression));
+
+    let ty = match tokens.as_slice() {
+        [name] if name.kind == TokenKind::Ident => scope::bindings_at(text, position, aliases)
+            .into_iter()
+            .find(|binding| binding.name == name.text)
+            .map(|binding| binding.ty),
+        [module, separator, name] if separator.is("::") => {
+            scope::module_constants(text, module.text)
+                .into_iter()
+                .find(|binding| binding.name == name.text)
+                .map(|binding| binding.ty)
+        }
+        [.., last] if last.is(")") => {
+            let open = scope::matching_open(&tokens, tokens.len() - 1)?;
+            match &tokens[..open] {
+                [name] => functions
+                    .get_func(name.text)?
+                    .ret()
+                    .map(ToString::to_string),
+                [module, separator, name] if module.is("jet") && separator.is("::") => {
+                    let element = Elements::from_str(name.text).ok()?;
+                    Some(jet::target_type(element).to_string())
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    ty.filter(|ty| !ty.is_empty())
+}
+
+/// Check if the cursor is in a type position: after `:` in a `let`, parameter, constant or `match`
+/// arm, after `->`, or inside the type argument of `unwrap_left`, `unwrap_right`, `is_none` and
+/// the `<Type>::into` cast.
+pub fn is_type_position(text: &str, position: Position) -> bool {
+    let tokens = lexer::tokenize(text);
+    let before = tokens_before(&tokens, position);
+
+    for (index, token) in before.iter().enumerate().rev() {
+        match token.text {
+            ":" | "->" => return annotation_continues(&before[index + 1..]),
+            "<" => match index.checked_sub(1).map(|previous| before[previous].text) {
+                Some("::") => {
+                    return index >= 2
+                        && matches!(
+                            before[index - 2].text,
+                            "unwrap_left" | "unwrap_right" | "is_none"
+                        )
+                        && annotation_continues(&before[index + 1..]);
+                }
+                Some("=" | "(" | "," | "{" | ";" | "=>") | None => {
+                    return annotation_continues(&before[index + 1..]);
+                }
+                // Generic argument of a type, e.g. `Either<`, so the annotation starts earlier.
+                _ => {}
+            },
+            "{" | "}" | "=" | "=>" => return false,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Check if `tokens` following the start of a type annotation can still be a part of the type.
+fn annotation_continues(tokens: &[Token]) -> bool {
+    let mut depth = 0usize;
+    for token in tokens {
+        match token.text {
+            "(" | "[" | "<" => depth += 1,
+            ")" | "]" | ">" => match depth.checked_sub(1) {
+                Some(next) => depth = next,
+                None => return false,
+            },
+            "," | ";" if depth == 0 => return false,
+            "," | ";" => {}
+            _ if matches!(token.kind, TokenKind::Ident | TokenKind::Number) => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Classify syntactic construct around the cursor.
+pub fn syntax_context(text: &str, position: Position) -> SyntaxContext {
+    let tokens = lexer::tokenize(text);
+    let before = tokens_before(&tokens, position);
+
+    let mut open_brackets = Vec::new();
+    for (index, token) in before.iter().enumerate() {
+        match token.text {
+            "{" | "(" | "[" => open_brackets.push(index),
+            "}" | ")" | "]" => {
+                open_brackets.pop();
+            }
+            _ => {}
+        }
+    }
+
+    let previous = before.last().map(|token| token.text);
+    let Some(&open) = open_brackets.last() else {
+        return match previous {
+            None | Some("}" | ";") => SyntaxContext::TopLevel,
+            Some(_) => SyntaxContext::Other,
+        };
+    };
+
+    match (before[open].text, previous) {
+        (_, Some("=" | "=>" | "(" | ",")) if !is_match_body(&before, open) => {
+            SyntaxContext::Expression
+        }
+        (_, Some("=>")) => SyntaxContext::Expression,
+        ("{", Some("{" | ";" | "}")) if is_module_body(&before, open) => SyntaxContext::Module,
+        ("{", Some("{" | ";" | "}")) if !is_match_body(&before, open) => SyntaxContext::Statement,
+        ("[", Some("[")) => SyntaxContext::Expression,
+        _ => SyntaxContext::Other,
+    }
+}
+
+/// Return combinator and index of its generic argument, if the cursor is inside `fold::<`,
+/// `array_fold::<` or `for_while::<`.
+pub fn combinator_argument(text: &str, position: Position) -> Option<(Combinator, usize)> {
+    let tokens = lexer::tokenize(text);
+    let before = tokens_before(&tokens, position);
+
+    let mut argument = 0;
+    for (index, token) in before.iter().enumerate().rev() {
+        match token.text {
+            "," => argument += 1,
+            "<" => {
+                let [.., name, separator] = &before[..index] else {
+                    return None;
+                };
+                if !separator.is("::") {
+                    return None;
+                }
+                return Combinator::from_name(name.text).map(|combinator| (combinator, argument));
+            }
+            _ if matches!(token.kind, TokenKind::Ident | TokenKind::Number) => {}
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+/// Return the `match` expression, if the cursor is at the start of one of its arms.
+pub fn match_arms(text: &str, position: Position) -> Option<MatchArms> {
+    let tokens = lexer::significant(&lexer::tokenize(text));
+    let before = tokens_before(&tokens, position);
+    if !before
+        .last()
+        .is_some_and(|token| token.is("{") || token.is(","))
+    {
+        return None;
+    }
+
+    let mut depth = 0usize;
+    let open = before.iter().rposition(|token| match token.text {
+        ")" | "]" | "}" => {
+            depth += 1;
+            false
+        }
+        "(" | "[" | "{" if depth > 0 => {
+            depth -= 1;
+            false
+        }
+        "(" | "[" | "{" => true,
+        _ => false,
+    })?;
+    if !is_match_body(&before, open) {
+        return None;
+    }
+    let keyword = before[..open].iter().rposition(|token| token.is("match"))?;
+
+    // Tokens before the cursor are a prefix of all tokens, so `open` indexes both.
+    let close = scope::matching_close(&tokens, open).unwrap_or(tokens.len());
+    let mut written = Vec::new();
+    let mut depth = 0usize;
+    for (index, token) in tokens.iter().enumerate().take(close).skip(open + 1) {
+        match token.text {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth = depth.saturating_sub(1),
+            // The identifier being typed at the cursor is not a written arm yet.
+            _ if depth == 0
+                && token.kind == TokenKind::Ident
+                && !(token.start <= position && position <= token.end)
+                && (tokens[index - 1].is("{") || tokens[index - 1].is(",")) =>
+            {
+                written.push(token.text.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    Some(MatchArms {
+        scrutinee: text[before[keyword].end_offset()..before[open].offset]
+            .trim()
+            .to_string(),
+        position: before[keyword].start,
+        written,
+    })
+}
+
+/// Check if the brace at `open` starts body of `mod witness` or `mod param`.
+fn is_module_body(tokens: &[Token], open: usize) -> bool {
+    open >= 2 && tokens[open - 2].is("mod")
+}
+
+/// Check if the brace at `open` starts arms of a `match` expression.
+fn is_match_body(tokens: &[Token], open: usize) -> bool {
+    if !tokens[open].is("{") {
+        return false;
+    }
+
+    let mut depth = 0usize;
+    for token in tokens[..open].iter().rev() {
+        match token.text {
+            ")" | "]" | "}" => depth += 1,
+            "(" | "[" | "{" if depth > 0 => depth -= 1,
+            "match" if depth == 0 => return true,
+            ";" | "{" | "=" | "=>" | "," | "(" | "->" if depth == 0 => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Return significant tokens before the cursor, without the identifier being typed and its module
+/// path, e.g. `jet::add`.
+pub fn tokens_before<'a>(tokens: &[Token<'a>], position: Position) -> Vec<Token<'a>> {
+    let mut before: Vec<Token> = tokens
+        .iter()
+        .filter(|token| !token.is_trivia() && token.end <= position)
+        .copied()
+        .collect();
+
+    if before
+        .last()
+        .is_some_and(|token| token.kind == TokenKind::Ident && token.end == position)
+    {
+        before.pop();
+    }
+    if before.last().is_some_and(|token| token.is("::")) {
+        before.pop();
+        if before
+            .last()
+            .is_some_and(|token| token.kind == TokenKind::Ident)
+        {
+            before.pop();
+        }
+    }
+
+    before
+}
+
+/// Walk tokens backwards from the cursor to the construct which determines the expected type.
+fn expected_type_of(text: &str, tokens: &[Token], functions: &Functions) -> Option<String> {
+    let mut depth = 0usize;
+    let mut argument = 0usize;
+    // Set after crossing `;`, when the cursor can only be at the start of a statement.
+    let mut statement_start = false;
+
+    for (index, token) in tokens.iter().enumerate().rev() {
+        match token.text {
+            ")" | "]" | "}" => depth += 1,
+            "(" | "[" | "{" if depth > 0 => depth -= 1,
+            "{" => return block_type(text, &tokens[..index], token),
+            _ if statement_start => {}
+            "(" => return argument_type(&tokens[..index], argument, functions),
+            "[" | "=>" => return None,
+            ";" if depth == 0 => statement_start = true,
+            "," if depth == 0 => argument += 1,
+            "=" if depth == 0 => return let_type(text, &tokens[..index], token),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Return type of the `index`-th argument of the call, which name ends `tokens`.
+fn argument_type(tokens: &[Token], index: usize, functions: &Functions) -> Option<String> {
+    let (name, path) = tokens.split_last()?;
+
+    if name.is("!") {
+        return path
+            .last()
+            .filter(|token| token.is("assert"))
+            .map(|_| "bool".to_string());
+    }
+    if name.kind != TokenKind::Ident {
+        return None;
+    }
+
+    match path {
+        [.., module, separator] if separator.is("::") => {
+            if !module.is("jet") {
+                return None;
+            }
+            let element = Elements::from_str(name.text).ok()?;
+            jet::source_type(element)
+                .get(index)
+                .map(ToString::to_string)
+        }
+        _ => functions
+            .get_func(name.text)?
+            .params()
+            .get(index)
+            .map(|param| param.ty().to_string()),
+    }
+}
+
+/// Return the annotated type of the `let` statement, which `=` is `equals`.
+fn let_type(text: &str, tokens: &[Token], equals: &Token) -> Option<String> {
+    let let_index = tokens.iter().rposition(|token| token.is("let"))?;
+    let statement = &tokens[let_index..];
+    if statement.iter().any(|token| token.is("{") || token.is("}")) {
+        return None;
+    }
+
+    let colon = statement.iter().find(|token| token.is(":"))?;
+    Some(text[colon.end_offset()..equals.offset].trim().to_string())
+}
+
+/// Return type of the block which opening brace is `brace`. Only function bodies have known type.
+fn block_type(text: &str, tokens: &[Token], brace: &Token) -> Option<String> {
+    let arrow = tokens.iter().rposition(|token| token.is("->"))?;
+    if tokens[arrow..]
+        .iter()
+        .any(|token| matches!(token.text, "{" | "}" | "=" | "=>"))
+    {
+        return None;
+    }
+
+    Some(
+        text[tokens[arrow].end_offset()..brace.offset]
+            .trim()
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::split_at_marker;
+
+    /// Return expected type at the `|` marker.
+    fn expected_at_marker(text: &str) -> Option<String> {
+        let (text, position) = split_at_marker(text);
+        expected_type(&text, position, &Functions::new())
+    }
+
+    #[test]
+    fn test_expected_type() {
+        assert_eq!(
+            expected_at_marker("fn main() { let x: (bool, u32) = jet::a|"),
+            Some("(bool, u32)".to_string())
+        );
+        assert_eq!(
+            expected_at_marker("fn main() { let x: u32 = jet::add_32(1, |"),
+            Some("u32".to_string())
+        );
+        assert_eq!(
+            expected_at_marker("fn f() -> u8 {\n    let a: [u8; 2] = [1, 2];\n    |"),
+            Some("u8".to_string())
+        );
+        assert_eq!(
+            expected_at_marker("fn main() { assert!(|"),
+            Some("bool".to_string())
+        );
+        assert_eq!(expected_at_marker("fn main() { let x: u8 = [|"), None);
+    }
+
+    fn is_type_position_at_marker(text: &str) -> bool {
+        let (text, position) = split_at_marker(text);
+        is_type_position(&text, position)
+    }
+
+    #[test]
+    fn test_is_type_position() {
+        assert!(is_type_position_at_marker("fn main() { let x: |"));
+        assert!(is_type_position_at_marker(
+            "fn main() { let x: Either<u8, Opt|"
+        ));
+        assert!(is_type_position_at_marker("fn f(a: u8, b: [u8; |"));
+        assert!(is_type_position_at_marker("fn f() -> |"));
+        assert!(is_type_position_at_marker("fn main() { unwrap_left::<|"));
+        assert!(is_type_position_at_marker("fn main() { let x: u8 = <|"));
+
+        assert!(!is_type_position_at_marker("fn f(a: u8, |"));
+        assert!(!is_type_position_at_marker("fn f(a: u8) |"));
+        assert!(!is_type_position_at_marker("fn main() { let x: u8 = |"));
+        assert!(!is_type_position_at_marker("fn main() { fold::<|"));
+    }
+
+    fn syntax_context_at_marker(text: &str) -> SyntaxContext {
+        let (text, position) = split_at_marker(text);
+        syntax_context(&text, position)
+    }
+
+    #[test]
+    fn test_syntax_context() {
+        assert_eq!(syntax_context_at_marker("|"), SyntaxContext::TopLevel);
+        assert_eq!(
+            syntax_context_at_marker("fn main() {}\nf|"),
+            SyntaxContext::TopLevel
+        );
+        assert_eq!(
+            syntax_context_at_marker("mod witness {\n    c|"),
+            SyntaxContext::Module
+        );
+        assert_eq!(
+            syntax_context_at_marker("fn main() {\n    l|"),
+            SyntaxContext::Statement
+        );
+        assert_eq!(
+            syntax_context_at_marker("fn main() { let x: u8 = |"),
+            SyntaxContext::Expression
+        );
+        assert_eq!(
+            syntax_context_at_marker("fn main() { match x {\n    |"),
+            SyntaxContext::Other
+        );
+        assert_eq!(
+            syntax_context_at_marker("fn main() { match x {\n    true => |"),
+            SyntaxContext::Expression
+        );
+        assert_eq!(syntax_context_at_marker("fn |"), SyntaxContext::Other);
+    }
+
+    fn combinator_at_marker(text: &str) -> Option<(Combinator, usize)> {
+        let (text, position) = split_at_marker(text);
+        combinator_argument(&text, position)
+    }
+
+    #[test]
+    fn test_combinator_argument() {
+        assert_eq!(
+            combinator_at_marker("fn main() { fold::<|"),
+            Some((Combinator::Fold, 0))
+        );
+        assert_eq!(
+            combinator_at_marker("fn main() { array_fold::<sum, 1|"),
+            Some((Combinator::ArrayFold, 1))
+        );
+        assert_eq!(
+            combinator_at_marker("fn main() { for_while::<st|"),
+            Some((Combinator::ForWhile, 0))
+        );
+        assert_eq!(combinator_at_marker("fn main() { unwrap_left::<|"), None);
+        assert_eq!(combinator_at_marker("fn main() { fold::<sum, 8>(|"), None);
+    }
+
+    #[test]
+    fn test_match_arms() {
+        let (text, position) =
+            split_at_marker("fn main() { match f(x, y) {\n    |\n    None => (),\n} }");
+        let arms = match_arms(&text, position).unwrap();
+        assert_eq!(arms.scrutinee, "f(x, y)");
+        assert_eq!(arms.position, Position::new(0, 12));
+        assert_eq!(arms.written, vec!["None".to_string()]);
+
+        let (text, position) = split_at_marker("fn main() { match x {\n    Left(a: u8) => |");
+        assert_eq!(match_arms(&text, position), None);
+    }
+}
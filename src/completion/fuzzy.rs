@@ -0,0 +1,270 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use tower_lsp_server::lsp_types::{CompletionItem, CompletionList};
+
+/// Maximum number of items sent in one completion response.
+pub const MAX_ITEMS: usize = 100;
+
+/// Score of the item, which matched only by the keywords of its documentation.
+const KEYWORD_SCORE: i64 = -100;
+
+/// Score how well `pattern` fuzzy-matches `candidate`, ignoring case.
+///
+/// Return `None` if characters of the pattern don't appear in the candidate in order. Higher
+/// score is better: matches at the start of the candidate or of a word after `_`, and consecutive
+/// matches get bonuses, while gaps and long candidates get penalties.
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0i64;
+    let mut next = 0usize;
+    let mut previous: Option<usize> = None;
+
+    for ch in pattern.chars() {
+        let index = (next..chars.len()).find(|&index| chars[index].eq_ignore_ascii_case(&ch))?;
+
+        score += 1;
+        if index == 0 {
+            score += 8;
+        } else if !chars[index - 1].is_alphanumeric() {
+            score += 6;
+        }
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 4;
+        }
+        score -= i64::try_from((index - next).min(3)).unwrap_or_default();
+
+        previous = Some(index);
+        next = index + 1;
+    }
+
+    Some(score - i64::try_from(chars.len() / 8).unwrap_or_default())
+}
+
+/// Completion item offered at the cursor, with its fit to the type expected there.
+pub struct Candidate<'a> {
+    pub item: Cow<'a, CompletionItem>,
+    /// Item fits the type expected at the cursor, so it is ranked before the others.
+    pub fits_type: bool,
+}
+
+impl From<CompletionItem> for Candidate<'_> {
+    fn from(item: CompletionItem) -> Self {
+        Self {
+            item: Cow::Owned(item),
+            fits_type: false,
+        }
+    }
+}
+
+impl<'a> From<&'a CompletionItem> for Candidate<'a> {
+    fn from(item: &'a CompletionItem) -> Self {
+        Self {
+            item: Cow::Borrowed(item),
+            fits_type: false,
+        }
+    }
+}
+
+/// Item which matched the identifier prefix, with the keys it is ranked by.
+struct Ranked<'a> {
+    /// 0 if the item fits the type expected at the cursor, 1 otherwise.
+    type_rank: u8,
+    score: i64,
+    /// Number of calls in the workspace.
+    used: usize,
+    /// Item matched only by the keywords of its documentation.
+    by_keyword: bool,
+    item: Cow<'a, CompletionItem>,
+}
+
+/// Filter items by the identifier prefix `word` and order them by type fit, match quality and
+/// how often they are used in the workspace.
+///
+/// Without a prefix there is nothing to match, so the `sortText` of the items, e.g. jets grouped
+/// by category, is the primary key after type fit.
+///
+/// Items which don't match the label are kept when `word` is found in their documentation
+/// `keywords`. The list is capped to [`MAX_ITEMS`] and marked incomplete, so the client asks
+/// again as the user types. Borrowed items are ranked in place and only the capped result is
+/// cloned.
+pub fn filter_and_rank<'a, 'b>(
+    items: impl IntoIterator<Item = Candidate<'b>>,
+    word: &str,
+    usage: &HashMap<String, usize>,
+    keywords: impl Fn(&CompletionItem) -> Option<&'a str>,
+) -> CompletionList {
+    let lowercase_word = word.to_lowercase();
+
+    let mut ranked: Vec<Ranked> = items
+        .into_iter()
+        .filter_map(|Candidate { item, fits_type }| {
+            let (score, by_keyword) =
+                match score(word, item.filter_text.as_deref().unwrap_or(&item.label)) {
+                    Some(score) => (score, false),
+                    None if word.len() >= 3
+                        && keywords(&item).is_some_and(|text| text.contains(&lowercase_word)) =>
+                    {
+                        (KEYWORD_SCORE, true)
+                    }
+                    None => return None,
+                };
+            let used = usage.get(&item.label).copied().unwrap_or_default();
+
+            Some(Ranked {
+                type_rank: u8::from(!fits_type),
+                score,
+                used,
+                by_keyword,
+                item,
+            })
+        })
+        .collect();
+
+    if word.is_empty() {
+        ranked.sort_by(|a, b| {
+            (
+                a.type_rank,
+                &a.item.sort_text,
+                Reverse(a.used),
+                &a.item.label,
+            )
+                .cmp(&(
+                    b.type_rank,
+                    &b.item.sort_text,
+                    Reverse(b.used),
+                    &b.item.label,
+                ))
+        });
+    } else {
+        ranked.sort_by(|a, b| {
+            (
+                a.type_rank,
+                Reverse(a.score),
+                Reverse(a.used),
+                &a.item.sort_text,
+                &a.item.label,
+            )
+                .cmp(&(
+                    b.type_rank,
+                    Reverse(b.score),
+                    Reverse(b.used),
+                    &b.item.sort_text,
+                    &b.item.label,
+                ))
+        });
+    }
+    ranked.truncate(MAX_ITEMS);
+
+    let items = ranked
+        .into_iter()
+        .enumerate()
+        .map(|(index, ranked)| {
+            let mut item = ranked.item.into_owned();
+            if ranked.by_keyword {
+                item.filter_text = Some(format!("{} {word}", item.label));
+            }
+            item.sort_text = Some(format!("{index:04}"));
+            item.preselect = (index == 0 && ranked.type_rank == 0).then_some(true);
+            item
+        })
+        .collect();
+
+    CompletionList {
+        is_incomplete: true,
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str) -> Candidate<'static> {
+        Candidate::from(CompletionItem {
+            label: label.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_score() {
+        assert!(score("sha", "sha_256_ctx_8_add_32").is_some());
+        assert!(score("s2a", "sha_256_ctx_8_add_32").is_some());
+        assert!(score("xyz", "sha_256_ctx_8_add_32").is_none());
+        assert!(score("add", "add_32") > score("add", "full_add_32"));
+        assert!(score("fa", "full_add_32") > score("fa", "sha_256_ctx_8_finalize"));
+    }
+
+    #[test]
+    fn test_filter_and_rank() {
+        let usage = HashMap::from([("add_16".to_string(), 3)]);
+        let list = filter_and_rank(
+            vec![
+                item("add_32"),
+                item("add_16"),
+                item("eq_32"),
+                item("sig_all_hash"),
+            ],
+            "add",
+            &usage,
+            |item| (item.label == "sig_all_hash").then_some("signature hash of all inputs"),
+        );
+
+        let labels: Vec<&str> = list.items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["add_16", "add_32"]);
+        assert!(list.is_incomplete);
+
+        let list = filter_and_rank(
+            vec![item("eq_32"), item("sig_all_hash")],
+            "hash",
+            &HashMap::new(),
+            |_| Some("keyword"),
+        );
+        assert_eq!(list.items[0].label, "sig_all_hash");
+    }
+
+    #[test]
+    fn test_empty_prefix_keeps_sort_text() {
+        let grouped = |label: &str, sort_text: &str| {
+            Candidate::from(CompletionItem {
+                sort_text: Some(sort_text.to_string()),
+                ..item(label).item.into_owned()
+            })
+        };
+        let list = filter_and_rank(
+            vec![
+                grouped("add_32", "01_add_32"),
+                grouped("sha_256_ctx_8_init", "02_sha_256_ctx_8_init"),
+                grouped("all_8", "00_all_8"),
+            ],
+            "",
+            &HashMap::from([("add_32".to_string(), 5)]),
+            |_| None,
+        );
+
+        let labels: Vec<&str> = list.items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["all_8", "add_32", "sha_256_ctx_8_init"]);
+    }
+
+    #[test]
+    fn test_type_fit() {
+        let sorted = Candidate::from(CompletionItem {
+            sort_text: Some("0_00000002".to_string()),
+            ..item("add_16").item.into_owned()
+        });
+        let fitting = Candidate {
+            fits_type: true,
+            ..item("add_32")
+        };
+        let list = filter_and_rank(vec![sorted, fitting], "add", &HashMap::new(), |_| None);
+
+        assert_eq!(list.items[0].label, "add_32");
+        assert_eq!(list.items[0].preselect, Some(true));
+        assert_eq!(list.items[1].preselect, None);
+
+        let list = filter_and_rank(vec![item("add_32")], "add", &HashMap::new(), |_| None);
+        assert_eq!(list.items[0].preselect, None);
+    }
+}
@@ -0,0 +1,86 @@
+use tower_lsp_server::lsp_types::{CompletionItem, CompletionItemKind, InsertTextFormat};
+
+use crate::completion::context::SyntaxContext;
+
+/// Get snippets of language constructs, which are grammatically valid in the given context.
+pub fn get_keyword_completions(context: SyntaxContext) -> Vec<CompletionItem> {
+    let snippets: &[(&str, &str, &str)] = match context {
+        SyntaxContext::TopLevel => &[
+            (
+                "fn",
+                "Function definition",
+                "fn ${1:name}(${2:param}: ${3:Type}) -> ${4:Type} {\n    $0\n}",
+            ),
+            (
+                "mod witness",
+                "Witness values of the program",
+                "mod witness {\n    const ${1:NAME}: ${2:Type} = ${3:value};\n}",
+            ),
+            (
+                "mod param",
+                "Parameters of the program",
+                "mod param {\n    const ${1:NAME}: ${2:Type} = ${3:value};\n}",
+            ),
+            ("type", "Type alias", "type ${1:Name} = ${2:Type};"),
+        ],
+        SyntaxContext::Module => &[(
+            "const",
+            "Constant declaration",
+            "const ${1:NAME}: ${2:Type} = ${3:value};",
+        )],
+        SyntaxContext::Statement => &[
+            (
+                "let",
+                "Variable binding",
+                "let ${1:pattern}: ${2:Type} = ${3:value};",
+            ),
+            (
+                "match",
+                "Match expression over `Either`",
+                MATCH_EITHER_SNIPPET,
+            ),
+            (
+                "match Option",
+                "Match expression over `Option`",
+                MATCH_OPTION_SNIPPET,
+            ),
+        ],
+        SyntaxContext::Expression => &[
+            (
+                "match",
+                "Match expression over `Either`",
+                MATCH_EITHER_SNIPPET,
+            ),
+            (
+                "match Option",
+                "Match expression over `Option`",
+                MATCH_OPTION_SNIPPET,
+            ),
+        ],
+        SyntaxContext::Other => &[],
+    };
+
+    snippets
+        .iter()
+        .map(|(label, detail, snippet)| snippet_to_completion(label, detail, snippet))
+        .collect()
+}
+
+/// Snippet of `match` expression over `Either` value.
+const MATCH_EITHER_SNIPPET: &str = "match ${1:expression} {\n    Left(${2:x}: ${3:L}) => ${4:()},\n    Right(${5:y}: ${6:R}) => ${7:()},\n}";
+
+/// Snippet of `match` expression over `Option` value.
+const MATCH_OPTION_SNIPPET: &str =
+    "match ${1:expression} {\n    None => ${2:()},\n    Some(${3:x}: ${4:T}) => ${5:()},\n}";
+
+/// Convert snippet to [`CompletionItem`].
+fn snippet_to_completion(label: &str, detail: &str, snippet: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some(detail.to_string()),
+        insert_text: Some(snippet.to_string()),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }
+}
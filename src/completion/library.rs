@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use simplicityhl::parse::{self, Function, ParseFromStr};
+use tower_lsp_server::lsp_types::Position;
+
+use crate::lexer::{self, TokenKind};
+
+/// Version of the bundled library, shown next to its functions in completion.
+pub const VERSION: &str = "1.0";
+
+/// Bundled functions with their documentation and source code.
+const LIBRARY: [(&str, &str); 8] = [
+    (
+        "Verify the BIP-340 signature `sig` of `pk` over the transaction using `SIGHASH_ALL`.",
+        "fn checksig(pk: Pubkey, sig: Signature) {
+    let msg: u256 = jet::sig_all_hash();
+    jet::bip_0340_verify((pk, msg), sig);
+}",
+    ),
+    (
+        "Verify the BIP-340 signature `sig` of `pk` over the transaction using `SIGHASH_ALL`, \
+         and require the transaction to be locked at least until block `height`.",
+        "fn checksig_after_height(pk: Pubkey, sig: Signature, height: Height) {
+    jet::check_lock_height(height);
+    let msg: u256 = jet::sig_all_hash();
+    jet::bip_0340_verify((pk, msg), sig);
+}",
+    ),
+    (
+        "Check if the transaction is locked at least until block `height`.",
+        "fn is_after_height(height: Height) -> bool {
+    jet::le_32(height, jet::tx_lock_height())
+}",
+    ),
+    (
+        "Check if the transaction is locked at least until UNIX timestamp `time`.",
+        "fn is_after_time(time: Time) -> bool {
+    jet::le_32(time, jet::tx_lock_time())
+}",
+    ),
+    (
+        "Require the transaction to have exactly `expected` outputs.",
+        "fn assert_num_outputs(expected: u32) {
+    assert!(jet::eq_32(jet::num_outputs(), expected));
+}",
+    ),
+    (
+        "Require the output at `index` to pay to the script with hash `expected`.",
+        "fn assert_output_script_hash(index: u32, expected: u256) {
+    let output_script_hash: u256 = unwrap(jet::output_script_hash(index));
+    assert!(jet::eq_256(output_script_hash, expected));
+}",
+    ),
+    (
+        "Require the output at `index` to pay to the script of the current input, \
+         so the funds stay locked by the same contract.",
+        "fn assert_recursive_output(index: u32) {
+    let this_script_hash: u256 = jet::current_script_hash();
+    let output_script_hash: u256 = unwrap(jet::output_script_hash(index));
+    assert!(jet::eq_256(this_script_hash, output_script_hash));
+}",
+    ),
+    (
+        "Require the output at `index` to be the transaction fee.",
+        "fn assert_output_is_fee(index: u32) {
+    assert!(unwrap(jet::output_is_fee(index)));
+}",
+    ),
+];
+
+/// Parse functions of the bundled library, returning each with its documentation and source.
+pub fn library_functions() -> Vec<(Function, &'static str, &'static str)> {
+    LIBRARY
+        .iter()
+        .filter_map(|(doc, definition)| {
+            let program = parse::Program::parse_from_str(definition).ok()?;
+            match program.items().first()? {
+                parse::Item::Function(func) => Some((func.clone(), *doc, *definition)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Return names of the top-level functions defined in the current `text`, which may not parse.
+pub fn defined_functions(text: &str) -> HashSet<&str> {
+    let tokens = lexer::significant(&lexer::tokenize(text));
+
+    let mut depth = 0usize;
+    let mut names = HashSet::new();
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => depth = depth.saturating_sub(1),
+            "fn" if depth == 0 => {
+                if let Some(name) = tokens
+                    .get(index + 1)
+                    .filter(|name| name.kind == TokenKind::Ident)
+                {
+                    names.insert(name.text);
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Return position where a library function used at `position` is inserted: before the
+/// top-level function enclosing the cursor and its doc comments.
+pub fn definition_position(text: &str, position: Position) -> Position {
+    let tokens = lexer::tokenize(text);
+
+    let mut depth = 0usize;
+    let mut function = None;
+    for (index, token) in tokens.iter().enumerate() {
+        if token.start >= position {
+            break;
+        }
+        match token.text {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => depth = depth.saturating_sub(1),
+            "fn" if depth == 0 && token.kind == TokenKind::Ident => function = Some(index),
+            _ => {}
+        }
+    }
+
+    let Some(mut start) = function else {
+        return Position::new(position.line, 0);
+    };
+    while let Some(previous) = start.checked_sub(1) {
+        match tokens[previous].kind {
+            TokenKind::DocComment | TokenKind::Whitespace
+                if !tokens[previous].text.contains("\n\n") =>
+            {
+                start = previous;
+            }
+            _ => break,
+        }
+    }
+    while tokens[start].kind == TokenKind::Whitespace {
+        start += 1;
+    }
+
+    Position::new(tokens[start].start.line, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simplicityhl::ast;
+
+    #[test]
+    fn test_library_functions() {
+        assert_eq!(library_functions().len(), LIBRARY.len());
+    }
+
+    #[test]
+    fn test_library_type_checks() {
+        for (_, definition) in LIBRARY {
+            let text = format!("{definition}\n\nfn main() {{}}\n");
+            let program =
+                parse::Program::parse_from_str(&text).expect("Expected library function to parse");
+            if let Err(error) = ast::Program::analyze(&program) {
+                panic!("Expected library function to type-check: {error}\n{definition}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_defined_functions() {
+        let text = "fn checksig(pk: Pubkey) {\n    let f: u8 = 0;\n}\n\nfn main() {\n    is_after_";
+        let names = defined_functions(text);
+        assert!(names.contains("checksig"));
+        assert!(names.contains("main"));
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn test_definition_position() {
+        let text = "fn f() {}\n\n/// Entry point\nfn main() {\n    check\n}";
+        assert_eq!(
+            definition_position(text, Position::new(4, 9)),
+            Position::new(2, 0)
+        );
+        assert_eq!(
+            definition_position(text, Position::new(0, 8)),
+            Position::new(0, 0)
+        );
+    }
+}
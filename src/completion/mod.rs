@@ -1,36 +1,119 @@
-use simplicityhl::parse::Function;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use miniscript::iter::TreeLike;
+use serde_json::Value;
+use simplicityhl::parse::{self, CallName, Function};
+use simplicityhl::simplicity::jet::{Elements, Jet};
 
 pub mod builtin;
+pub mod builtin_type;
+pub mod combinator;
+pub mod context;
+pub mod fuzzy;
 pub mod jet;
+mod jet_catalogue;
+pub mod keyword;
+pub mod library;
+pub mod pattern;
+pub mod snippet;
 pub mod types;
 
+use builtin::CallSite;
+use context::ExpectedType;
+use fuzzy::Candidate;
+
+use crate::function::Functions;
+use crate::scope::{Binding, BindingKind};
+
 use tower_lsp_server::lsp_types::{
-    CompletionItem, CompletionItemKind, Documentation, InsertTextFormat, MarkupContent, MarkupKind,
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionResponse,
+    Documentation, InsertTextFormat, MarkupContent, MarkupKind, Position, Range, TextEdit,
 };
 
 /// Build and provide [`CompletionItem`] for jets and builtin functions.
+///
+/// Items are built once and shared between requests without their documentation, which is filled
+/// in lazily by `completionItem/resolve`.
 #[derive(Debug)]
 pub struct CompletionProvider {
-    /// All jets completions.
-    jets: Vec<CompletionItem>,
+    /// All jets completions, paired with the jet return type.
+    jets: Arc<[(CompletionItem, String)]>,
 
-    /// All builtin functions completions.
-    builtin: Vec<CompletionItem>,
+    /// All builtin functions completions, paired with the call they complete.
+    builtin: Arc<[(CompletionItem, CallName)]>,
 
     /// Modules completions.
-    modules: Vec<CompletionItem>,
+    modules: Arc<[CompletionItem]>,
+
+    /// Builtin types completions.
+    types: Arc<[CompletionItem]>,
+
+    /// Bundled library functions completions, paired with the return type and the definition.
+    library: Arc<[(CompletionItem, String, &'static str)]>,
+
+    /// Documentation of jets and builtin functions, keyed by `data` of the completion item.
+    documentation: Arc<HashMap<String, Documentation>>,
+
+    /// Lowercase documentation and category of jets and builtin functions, used to find items
+    /// by words which are not in their labels.
+    keywords: Arc<HashMap<String, String>>,
 }
 
 impl CompletionProvider {
     /// Create new [`CompletionProvider`] with evaluated jets and builtins completions.
     pub fn new() -> Self {
-        let jets_completion = jet::get_jets_completions()
+        let mut documentation = HashMap::new();
+        let mut keywords = HashMap::new();
+
+        let jets_completion = Elements::ALL
             .iter()
-            .map(template_to_completion)
+            .map(|jet| {
+                (
+                    defer_documentation(
+                        jet_to_completion(*jet),
+                        "jet",
+                        &mut documentation,
+                        &mut keywords,
+                    ),
+                    jet::target_type(*jet).to_string(),
+                )
+            })
+            .collect();
+        let builtin_completion = builtin::get_builtin_calls()
+            .into_iter()
+            .filter_map(|call| {
+                let template = builtin::match_callname(&call)?;
+                Some((
+                    defer_documentation(
+                        template_to_completion(&template),
+                        "builtin",
+                        &mut documentation,
+                        &mut keywords,
+                    ),
+                    call,
+                ))
+            })
             .collect();
-        let builtin_completion = builtin::get_builtin_functions()
+
+        let library_completion = library::library_functions()
             .iter()
-            .map(template_to_completion)
+            .map(|(func, doc, definition)| {
+                let template = function_to_template(func, doc);
+                let item = CompletionItem {
+                    label_details: Some(CompletionItemLabelDetails {
+                        detail: None,
+                        description: Some(format!("library v{}", library::VERSION)),
+                    }),
+                    ..template_to_completion(&template)
+                };
+                (
+                    defer_documentation(item, "library", &mut documentation, &mut keywords),
+                    template.return_type,
+                    *definition,
+                )
+            })
             .collect();
 
         let modules_completion = [
@@ -45,31 +128,131 @@ impl CompletionProvider {
             jets: jets_completion,
             builtin: builtin_completion,
             modules: modules_completion,
+            types: builtin_type::get_builtin_types().into(),
+            library: library_completion,
+            documentation: Arc::new(documentation),
+            keywords: Arc::new(keywords),
         }
     }
 
-    /// Return jets completions.
-    pub fn jets(&self) -> &[CompletionItem] {
-        &self.jets
+    /// Return jets completions, ranked by the type expected at the cursor.
+    pub fn jets<'a>(
+        &'a self,
+        expected: Option<&ExpectedType>,
+    ) -> impl Iterator<Item = Candidate<'a>> {
+        self.jets.iter().map(move |(item, return_type)| {
+            rank_by_type(Cow::Borrowed(item), return_type, expected)
+        })
     }
 
-    /// Return builtin functions completions.
-    pub fn builtins(&self) -> &[CompletionItem] {
-        &self.builtin
+    /// Filter and rank items by the identifier being typed, see [`fuzzy::filter_and_rank`].
+    pub fn filter<'a>(
+        &self,
+        items: impl IntoIterator<Item = Candidate<'a>>,
+        word: &str,
+        usage: &HashMap<String, usize>,
+    ) -> CompletionResponse {
+        CompletionResponse::List(fuzzy::filter_and_rank(
+            items,
+            word,
+            usage,
+            |item| match &item.data {
+                Some(Value::String(key)) => self.keywords.get(key).map(String::as_str),
+                _ => None,
+            },
+        ))
     }
 
-    /// Return builtin functions completions.
-    pub fn modules(&self) -> &[CompletionItem] {
-        &self.modules
+    /// Fill in documentation of the item, which was deferred when the item was built.
+    pub fn resolve(&self, mut item: CompletionItem) -> CompletionItem {
+        if item.documentation.is_none() {
+            item.documentation = match &item.data {
+                Some(Value::String(key)) => self.documentation.get(key).cloned(),
+                _ => None,
+            };
+        }
+        item
+    }
+
+    /// Return completions of library functions, which are not defined in the current `text`.
+    ///
+    /// Accepting an item also inserts the function definition at `definition_at`.
+    pub fn library(
+        &self,
+        text: &str,
+        definition_at: Position,
+        expected: Option<&ExpectedType>,
+    ) -> Vec<Candidate<'static>> {
+        let defined = library::defined_functions(text);
+        self.library
+            .iter()
+            .filter(|(item, _, _)| !defined.contains(item.label.as_str()))
+            .map(|(item, return_type, definition)| {
+                let item = CompletionItem {
+                    additional_text_edits: Some(vec![TextEdit {
+                        range: Range::new(definition_at, definition_at),
+                        new_text: format!("{definition}\n\n"),
+                    }]),
+                    ..item.clone()
+                };
+                rank_by_type(Cow::Owned(item), return_type, expected)
+            })
+            .collect()
+    }
+
+    /// Return builtin functions completions with signatures instantiated by the type expected at
+    /// the cursor, ranked by it.
+    ///
+    /// Without the expected type the items are borrowed, as their signatures have nothing to
+    /// instantiate.
+    pub fn builtins<'a>(
+        &'a self,
+        functions: &Functions,
+        expected: Option<&ExpectedType>,
+    ) -> impl Iterator<Item = Candidate<'a>> {
+        let site = CallSite {
+            argument: None,
+            expected: expected.map(|expected| expected.ty.clone()),
+        };
+        self.builtin.iter().map(move |(item, call)| {
+            let Some(expected) = expected else {
+                return Candidate::from(item);
+            };
+            let Some(template) = builtin::instantiate_callname(call, functions, &site) else {
+                return Candidate::from(item);
+            };
+            let item = CompletionItem {
+                detail: Some(template.get_signature()),
+                ..item.clone()
+            };
+            rank_by_type(Cow::Owned(item), &template.return_type, Some(expected))
+        })
+    }
+
+    /// Return modules completions.
+    pub fn modules(&self) -> impl Iterator<Item = Candidate<'_>> {
+        self.modules.iter().map(Candidate::from)
     }
 
-    /// Get generic functions completions.
-    pub fn get_function_completions(functions: &[(&Function, &str)]) -> Vec<CompletionItem> {
+    /// Return builtin types completions.
+    pub fn types(&self) -> impl Iterator<Item = Candidate<'_>> {
+        self.types.iter().map(Candidate::from)
+    }
+
+    /// Get generic functions completions, ranked by the type expected at the cursor.
+    pub fn get_function_completions(
+        functions: &[(&Function, &str)],
+        expected: Option<&ExpectedType>,
+    ) -> Vec<Candidate<'static>> {
         functions
             .iter()
             .map(|(func, doc)| {
                 let template = function_to_template(func, doc);
-                template_to_completion(&template)
+                rank_by_type(
+                    Cow::Owned(template_to_completion(&template)),
+                    &template.return_type,
+                    expected,
+                )
             })
             .collect()
     }
@@ -88,6 +271,75 @@ pub fn function_to_template(func: &Function, doc: &str) -> types::FunctionTempla
     )
 }
 
+/// Move documentation of the item to `documentation`, leaving its key in `data` to resolve it.
+///
+/// Documentation and label description are also stored in lowercase to `keywords`.
+fn defer_documentation(
+    mut item: CompletionItem,
+    namespace: &str,
+    documentation: &mut HashMap<String, Documentation>,
+    keywords: &mut HashMap<String, String>,
+) -> CompletionItem {
+    if let Some(doc) = item.documentation.take() {
+        let key = format!("{namespace}::{}", item.label);
+        item.data = Some(Value::String(key.clone()));
+
+        let mut text = match &doc {
+            Documentation::String(text) => text.to_lowercase(),
+            Documentation::MarkupContent(content) => content.value.to_lowercase(),
+        };
+        if let Some(description) = item
+            .label_details
+            .as_ref()
+            .and_then(|details| details.description.as_ref())
+        {
+            text.push('\n');
+            text.push_str(&description.to_lowercase());
+        }
+
+        keywords.insert(key.clone(), text);
+        documentation.insert(key, doc);
+    }
+    item
+}
+
+/// Count how often each function, jet and builtin is called, keyed by its completion label.
+pub fn count_calls(functions: &[&Function]) -> HashMap<String, usize> {
+    let mut usage = HashMap::new();
+
+    for func in functions {
+        for expr in parse::ExprTree::Expression(func.body()).pre_order_iter() {
+            let parse::ExprTree::Call(call) = expr else {
+                continue;
+            };
+            let label = match call.name() {
+                CallName::Jet(jet) => jet.to_string(),
+                CallName::Custom(name) => name.to_string(),
+                other => match builtin::match_callname(other) {
+                    Some(template) => template.display_name,
+                    None => continue,
+                },
+            };
+            *usage.entry(label).or_default() += 1;
+        }
+    }
+
+    usage
+}
+
+/// Mark item as fitting the expected type, if its return type fits, so it is ranked before the
+/// others.
+fn rank_by_type<'a>(
+    item: Cow<'a, CompletionItem>,
+    return_type: &str,
+    expected: Option<&ExpectedType>,
+) -> Candidate<'a> {
+    Candidate {
+        item,
+        fits_type: expected.is_some_and(|expected| expected.fits(return_type)),
+    }
+}
+
 /// Convert [`types::FunctionTemplate`] to [`CompletionItem`].
 fn template_to_completion(func: &types::FunctionTemplate) -> CompletionItem {
     CompletionItem {
@@ -110,6 +362,52 @@ fn template_to_completion(func: &types::FunctionTemplate) -> CompletionItem {
     }
 }
 
+/// Convert [`Elements`] to [`CompletionItem`], showing the jet category and cost next to its
+/// label. Items are sorted by category, so jets of the same group are listed together.
+fn jet_to_completion(jet: Elements) -> CompletionItem {
+    let category = jet::category(jet);
+    CompletionItem {
+        label_details: Some(CompletionItemLabelDetails {
+            detail: None,
+            description: Some(format!("{category} · cost {}", jet.cost())),
+        }),
+        sort_text: Some(format!("{:02}_{jet}", category as u8)),
+        ..template_to_completion(&jet::jet_to_template(jet))
+    }
+}
+
+/// Convert bindings visible at the cursor to [`CompletionItem`], ranked by the expected type.
+pub fn get_binding_completions(
+    bindings: &[Binding],
+    expected: Option<&ExpectedType>,
+) -> Vec<Candidate<'static>> {
+    bindings
+        .iter()
+        .map(|binding| {
+            rank_by_type(
+                Cow::Owned(binding_to_completion(binding)),
+                &binding.ty,
+                expected,
+            )
+        })
+        .collect()
+}
+
+/// Convert [`Binding`] to [`CompletionItem`] with its type in `detail`.
+fn binding_to_completion(binding: &Binding) -> CompletionItem {
+    CompletionItem {
+        label: binding.name.clone(),
+        kind: Some(match binding.kind {
+            BindingKind::Parameter | BindingKind::Variable => CompletionItemKind::VARIABLE,
+            BindingKind::Witness | BindingKind::Param => CompletionItemKind::CONSTANT,
+        }),
+        detail: (!binding.ty.is_empty()).then(|| binding.ty.clone()),
+        insert_text: Some(binding.name.clone()),
+        insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+        ..Default::default()
+    }
+}
+
 /// Convert module name to [`CompletionItem`].
 fn module_to_completion(module: String, detail: String) -> CompletionItem {
     CompletionItem {
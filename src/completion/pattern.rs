@@ -0,0 +1,194 @@
+use tower_lsp_server::lsp_types::{CompletionItem, CompletionItemKind, InsertTextFormat};
+
+use crate::completion::context::{self, ExpectedType, MatchArms};
+use crate::function::Functions;
+use crate::type_utils::{self, Aliases};
+
+/// Constructor of `Either` or `Option` value with the type it wraps and the name of its binding.
+struct Constructor {
+    name: &'static str,
+    binding: &'static str,
+    ty: Option<String>,
+}
+
+/// Return constructors of `Either` or `Option` type, resolving aliases if needed.
+fn constructors(ty: &str, aliases: &Aliases) -> Option<[Constructor; 2]> {
+    let resolved = type_utils::resolve_alias(ty, aliases);
+
+    for ty in [ty, resolved.as_str()] {
+        if let Some([left, right]) = type_utils::generic_arguments(ty, "Either").as_deref() {
+            return Some([
+                Constructor {
+                    name: "Left",
+                    binding: "left",
+                    ty: Some(left.clone()),
+                },
+                Constructor {
+                    name: "Right",
+                    binding: "right",
+                    ty: Some(right.clone()),
+                },
+            ]);
+        }
+        if let Some([element]) = type_utils::generic_arguments(ty, "Option").as_deref() {
+            return Some([
+                Constructor {
+                    name: "Some",
+                    binding: "value",
+                    ty: Some(element.clone()),
+                },
+                Constructor {
+                    name: "None",
+                    binding: "",
+                    ty: None,
+                },
+            ]);
+        }
+    }
+
+    None
+}
+
+/// Find type of the matched expression, see [`context::expression_type`].
+pub fn scrutinee_type(
+    text: &str,
+    arms: &MatchArms,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Option<String> {
+    context::expression_type(text, &arms.scrutinee, arms.position, functions, aliases)
+}
+
+/// Get snippets of the arms of `match` over value of type `ty`: one with all arms, if none is
+/// written yet, and one for each missing arm.
+pub fn get_arm_completions(ty: &str, written: &[String], aliases: &Aliases) -> Vec<CompletionItem> {
+    let Some(constructors) = constructors(ty, aliases) else {
+        return vec![];
+    };
+
+    let mut completions = Vec::new();
+    if written.is_empty() {
+        let mut placeholder = 1;
+        let arms: Vec<String> = constructors
+            .iter()
+            .map(|constructor| arm_snippet(constructor, &mut placeholder))
+            .collect();
+
+        completions.push(CompletionItem {
+            label: format!("{}, {}", constructors[0].name, constructors[1].name),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: Some(format!("All arms of `{ty}`")),
+            filter_text: Some(format!("{} {}", constructors[0].name, constructors[1].name)),
+            insert_text: Some(arms.join("\n")),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            ..Default::default()
+        });
+    }
+
+    completions.extend(
+        constructors
+            .iter()
+            .filter(|constructor| !written.iter().any(|name| name == constructor.name))
+            .map(|constructor| CompletionItem {
+                label: constructor.name.to_string(),
+                kind: Some(CompletionItemKind::SNIPPET),
+                detail: Some(format!("Arm of `{ty}`")),
+                insert_text: Some(arm_snippet(constructor, &mut 1)),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            }),
+    );
+
+    completions
+}
+
+/// Return snippet of the arm, numbering its placeholders from `placeholder`.
+fn arm_snippet(constructor: &Constructor, placeholder: &mut usize) -> String {
+    let first = *placeholder;
+    match &constructor.ty {
+        Some(ty) => {
+            *placeholder += 2;
+            format!(
+                "{}(${{{first}:{}}}: {ty}) => ${{{}:()}},",
+                constructor.name,
+                constructor.binding,
+                first + 1,
+            )
+        }
+        None => {
+            *placeholder += 1;
+            format!("{} => ${{{first}:()}},", constructor.name)
+        }
+    }
+}
+
+/// Get constructors `Left` and `Right` or `Some` and `None`, if the expected type is `Either` or
+/// `Option`. They fit the expected type by construction.
+pub fn get_constructor_completions(expected: &ExpectedType) -> Vec<CompletionItem> {
+    let Some(constructors) = constructors(&expected.ty, expected.aliases) else {
+        return vec![];
+    };
+
+    constructors
+        .iter()
+        .map(|constructor| {
+            let (detail, insert_text) = match &constructor.ty {
+                Some(ty) => (
+                    format!("{}({ty}) -> {}", constructor.name, expected.ty),
+                    format!("{}($0)", constructor.name),
+                ),
+                None => (
+                    format!("{} -> {}", constructor.name, expected.ty),
+                    constructor.name.to_string(),
+                ),
+            };
+            CompletionItem {
+                label: constructor.name.to_string(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(detail),
+                insert_text: Some(insert_text),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arm_completions() {
+        let completions = get_arm_completions("Either<u8, (u1, u256)>", &[], &Aliases::new());
+        assert_eq!(
+            completions[0].insert_text.as_deref(),
+            Some("Left(${1:left}: u8) => ${2:()},\nRight(${3:right}: (u1, u256)) => ${4:()},")
+        );
+        assert_eq!(completions.len(), 3);
+
+        let completions =
+            get_arm_completions("Option<Pubkey>", &["Some".to_string()], &Aliases::new());
+        assert_eq!(completions.len(), 1);
+        assert_eq!(
+            completions[0].insert_text.as_deref(),
+            Some("None => ${1:()},")
+        );
+
+        assert!(get_arm_completions("u8", &[], &Aliases::new()).is_empty());
+    }
+
+    #[test]
+    fn test_constructor_completions() {
+        let aliases = Aliases::new();
+        let expected = ExpectedType {
+            ty: "Amount1".to_string(),
+            aliases: &aliases,
+        };
+        let labels: Vec<String> = get_constructor_completions(&expected)
+            .into_iter()
+            .map(|item| item.label)
+            .collect();
+        assert_eq!(labels, vec!["Left", "Right"]);
+    }
+}
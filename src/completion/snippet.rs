@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ropey::Rope;
+use serde::Deserialize;
+use tower_lsp_server::UriExt;
+use tower_lsp_server::lsp_types::{
+    CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, Documentation,
+    InsertTextFormat, MarkupContent, MarkupKind, Range, Uri,
+};
+
+use crate::utils::byte_to_position;
+
+/// Directory of the workspace, which contains snippet packs.
+pub const SNIPPETS_DIR: &str = ".simplicityhl/snippets";
+
+/// Glob pattern of snippet packs, which are watched for changes.
+pub const SNIPPETS_GLOB: &str = "**/.simplicityhl/snippets/*.toml";
+
+/// Snippet pack file, e.g.
+///
+/// ```toml
+/// [[snippet]]
+/// label = "owner_checksig"
+/// description = "Check signature of the owner"
+/// body = "checksig(param::OWNER, witness::${1:SIGNATURE});"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnippetPack {
+    #[serde(default)]
+    snippet: Vec<Snippet>,
+}
+
+/// Snippet definition, where `body` uses the LSP snippet syntax.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Snippet {
+    label: String,
+    body: String,
+    detail: Option<String>,
+    description: Option<String>,
+}
+
+/// Snippets loaded from the workspace.
+#[derive(Debug, Default)]
+pub struct Snippets {
+    pub items: Vec<CompletionItem>,
+    /// Loaded files with their diagnostics, which are empty for files parsed successfully.
+    pub files: Vec<(Uri, Vec<Diagnostic>)>,
+}
+
+/// Load snippet packs from [`SNIPPETS_DIR`] of every workspace folder in `roots`.
+pub fn load(roots: &[PathBuf]) -> Snippets {
+    let mut snippets = Snippets::default();
+    for root in roots {
+        load_root(root, &mut snippets);
+    }
+    snippets
+}
+
+/// Load snippet packs from [`SNIPPETS_DIR`] of the workspace folder `root` into `snippets`.
+fn load_root(root: &Path, snippets: &mut Snippets) {
+    let Ok(entries) = fs::read_dir(root.join(SNIPPETS_DIR)) else {
+        return;
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "toml")
+        })
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let Some(uri) = Uri::from_file_path(&path) else {
+            continue;
+        };
+        let pack = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let diagnostics = match fs::read_to_string(&path) {
+            Ok(text) => match parse_pack(&text, &pack) {
+                Ok(items) => {
+                    snippets.items.extend(items);
+                    vec![]
+                }
+                Err(diagnostic) => vec![diagnostic],
+            },
+            Err(err) => vec![error_diagnostic(
+                Range::default(),
+                format!("Failed to read snippet pack: {err}"),
+            )],
+        };
+        snippets.files.push((uri, diagnostics));
+    }
+}
+
+/// Parse snippet pack named `pack`, returning diagnostic at the error if it is invalid.
+pub fn parse_pack(text: &str, pack: &str) -> Result<Vec<CompletionItem>, Diagnostic> {
+    let parsed: SnippetPack = toml::from_str(text).map_err(|err| {
+        let range = err.span().map_or_else(Range::default, |span| {
+            let rope = Rope::from_str(text);
+            Range::new(
+                byte_to_position(&rope, span.start),
+                byte_to_position(&rope, span.end),
+            )
+        });
+        error_diagnostic(range, format!("Invalid snippet pack: {}", err.message()))
+    })?;
+
+    Ok(parsed
+        .snippet
+        .into_iter()
+        .map(|snippet| snippet_to_completion(snippet, pack))
+        .collect())
+}
+
+/// Create error [`Diagnostic`] with the message.
+fn error_diagnostic(range: Range, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Some(DiagnosticSeverity::ERROR),
+        ..Diagnostic::new_simple(range, message)
+    }
+}
+
+/// Convert [`Snippet`] to [`CompletionItem`].
+fn snippet_to_completion(snippet: Snippet, pack: &str) -> CompletionItem {
+    CompletionItem {
+        label: snippet.label,
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some(
+            snippet
+                .detail
+                .unwrap_or_else(|| format!("Snippet from `{pack}`")),
+        ),
+        documentation: snippet.description.map(|value| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            })
+        }),
+        insert_text: Some(snippet.body),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pack() {
+        let items = parse_pack(
+            "[[snippet]]\nlabel = \"owner\"\nbody = \"checksig(param::OWNER, ${1:sig});\"\n",
+            "team",
+        )
+        .expect("Expected valid snippet pack");
+        assert_eq!(items[0].label, "owner");
+        assert_eq!(items[0].detail.as_deref(), Some("Snippet from `team`"));
+
+        let diagnostic = parse_pack("[[snippet]]\nlabel = \"owner\"\nbody = 1\n", "team")
+            .expect_err("Expected invalid snippet pack");
+        assert_eq!(diagnostic.range.start.line, 2);
+    }
+}
@@ -65,6 +65,17 @@ impl FunctionTemplate {
         }
     }
 
+    /// Get function name with generic arguments, as it is written at the call site
+    pub fn get_generic_name(&self) -> String {
+        if self.generics.is_empty() {
+            self.display_name.clone()
+        } else if self.display_name == "into" {
+            format!("<{}>::into", self.generics.join(", "))
+        } else {
+            format!("{}::<{}>", self.display_name, self.generics.join(", "))
+        }
+    }
+
     /// Get text, which would inserted when completion triggered
     pub fn get_insert_text(&self) -> String {
         format!(
@@ -0,0 +1,178 @@
+use miniscript::iter::TreeLike;
+use simplicityhl::error::Span;
+use simplicityhl::parse::{self, ParseFromStr};
+use tower_lsp_server::lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope;
+
+/// Return folding ranges of the document: functions, `match` expressions and arms and nested
+/// blocks from the parsed program, `mod` blocks and runs of `///` comments.
+///
+/// If the document can't be parsed, every block in braces is folded instead.
+pub fn folding_ranges(text: &str) -> Vec<FoldingRange> {
+    let tokens = lexer::tokenize(text);
+    let significant = lexer::significant(&tokens);
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut ranges = match parse::Program::parse_from_str(text) {
+        Ok(program) => program_ranges(&program, &lines),
+        Err(_) => brace_ranges(&significant, |_| true),
+    };
+    ranges.extend(brace_ranges(&significant, |open| {
+        open.checked_sub(2)
+            .is_some_and(|keyword| significant[keyword].is("mod"))
+    }));
+    ranges.extend(comment_ranges(&tokens));
+
+    // Ranges starting on the same line can't be folded separately, so the outermost is kept.
+    ranges.sort_by_key(|range| (range.start_line, std::cmp::Reverse(range.end_line)));
+    ranges.dedup_by_key(|range| range.start_line);
+    ranges
+}
+
+/// Return folding range between the lines, if it spans more than one line.
+fn region(start_line: u32, end_line: u32, kind: FoldingRangeKind) -> Option<FoldingRange> {
+    (end_line > start_line).then_some(FoldingRange {
+        start_line,
+        end_line,
+        kind: Some(kind),
+        ..Default::default()
+    })
+}
+
+/// Return range folding the span, which keeps a closing bracket on its own line visible.
+fn span_range(span: &Span, lines: &[&str]) -> Option<FoldingRange> {
+    // Only lines are needed, which count the same in spans and in LSP positions.
+    let start = u32::try_from(span.start.line.get() - 1).ok()?;
+    let end = u32::try_from(span.end.line.get() - 1).ok()?;
+    let closing = lines
+        .get(end as usize)
+        .and_then(|line| line.trim_start().chars().next())
+        .is_some_and(|ch| matches!(ch, '}' | ')' | ']'));
+
+    region(
+        start,
+        end.saturating_sub(u32::from(closing)),
+        FoldingRangeKind::Region,
+    )
+}
+
+/// Return ranges of functions and of expressions of their bodies spanning several lines.
+fn program_ranges(program: &parse::Program, lines: &[&str]) -> Vec<FoldingRange> {
+    program
+        .items()
+        .iter()
+        .filter_map(|item| match item {
+            parse::Item::Function(func) => Some(func),
+            _ => None,
+        })
+        .flat_map(|func| {
+            let expressions = parse::ExprTree::Expression(func.body())
+                .pre_order_iter()
+                .filter_map(|expr| match expr {
+                    parse::ExprTree::Expression(expression) => Some(expression.as_ref()),
+                    parse::ExprTree::Single(single) => Some(single.as_ref()),
+                    _ => None,
+                })
+                .filter_map(|span| span_range(span, lines))
+                .collect::<Vec<_>>();
+            span_range(func.span(), lines)
+                .into_iter()
+                .chain(expressions)
+        })
+        .collect()
+}
+
+/// Return ranges of blocks in braces, which opening brace at index of `tokens` is accepted.
+fn brace_ranges(tokens: &[Token], accept: impl Fn(usize) -> bool) -> Vec<FoldingRange> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(index, token)| token.is("{") && accept(*index))
+        .filter_map(|(open, token)| {
+            let close = scope::matching_close(tokens, open)?;
+            region(
+                token.start.line,
+                tokens[close].start.line.saturating_sub(1),
+                FoldingRangeKind::Region,
+            )
+        })
+        .collect()
+}
+
+/// Return ranges of consecutive lines of `///` comments.
+fn comment_ranges(tokens: &[Token]) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+    let mut run: Option<(u32, u32)> = None;
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::DocComment => {
+                run = match run {
+                    Some((start, end)) if token.start.line == end + 1 => {
+                        Some((start, token.start.line))
+                    }
+                    _ => {
+                        ranges.extend(run.and_then(|(start, end)| {
+                            region(start, end, FoldingRangeKind::Comment)
+                        }));
+                        Some((token.start.line, token.start.line))
+                    }
+                };
+            }
+            TokenKind::Whitespace => {}
+            _ => {
+                ranges.extend(
+                    run.take()
+                        .and_then(|(start, end)| region(start, end, FoldingRangeKind::Comment)),
+                );
+            }
+        }
+    }
+    ranges.extend(run.and_then(|(start, end)| region(start, end, FoldingRangeKind::Comment)));
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(ranges: &[FoldingRange]) -> Vec<(u32, u32)> {
+        ranges
+            .iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn test_folding_ranges() {
+        let text = "mod witness {
+    const A: u8 = 0x01;
+}
+
+/// Check the value.
+/// Panics otherwise.
+fn check(x: Option<u8>) {
+    match x {
+        Some(y: u8) => {
+            assert!(jet::eq_8(y, witness::A));
+        },
+        None => {},
+    };
+}
+
+fn main() {}";
+        assert_eq!(
+            lines(&folding_ranges(text)),
+            vec![(0, 1), (4, 5), (6, 12), (7, 11), (8, 9)]
+        );
+
+        // Unbalanced parenthesis makes the program invalid.
+        let invalid = text.replace("fn main() {}", "fn main() { (\n}");
+        assert_eq!(
+            lines(&folding_ranges(&invalid)),
+            vec![(0, 1), (4, 5), (6, 12), (7, 11), (8, 9)]
+        );
+    }
+}
@@ -0,0 +1,712 @@
+//! Formatter of `SimplicityHL` programs.
+//!
+//! The layout is built on [`parse::Program`]: the expression tree of every function tells which
+//! braces open blocks, whose statements are written one per line, and which open `match`
+//! bodies, whose arms are written one per line. The text of the lines comes from the token
+//! stream of [`crate::lexer`], so comments and blank lines, which the parse tree drops, keep
+//! their places. The output must parse and contain the same tokens and comments as the input.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use miniscript::iter::TreeLike;
+use ropey::Rope;
+use simplicityhl::parse::{self, ParseFromStr};
+
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope;
+use crate::utils::{position_to_byte, span_to_positions};
+
+/// Keywords, which are separated by a space from a following opening parenthesis.
+const KEYWORDS: [&str; 7] = ["let", "match", "const", "type", "mod", "fn", "return"];
+
+/// Options of the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Number of columns of one indentation level.
+    pub indent_width: usize,
+    /// Indent with tabs instead of spaces.
+    pub use_tabs: bool,
+    /// Lines longer than this are broken at commas of their last argument list.
+    pub max_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            use_tabs: false,
+            max_width: 100,
+        }
+    }
+}
+
+/// Format `SimplicityHL` program, keeping its comments.
+///
+/// Return `None` if the program can't be parsed, or if formatting would change anything besides
+/// whitespace.
+pub fn format(text: &str, options: &FormatOptions) -> Option<String> {
+    let program = parse::Program::parse_from_str(text).ok()?;
+
+    let tokens = lexer::tokenize(text);
+    let formatted = Printer::new(*options, match_bodies(&program, text)).print(&tokens);
+
+    let content = |tokens: &[Token<'_>]| -> Vec<String> {
+        tokens
+            .iter()
+            .filter(|token| token.kind != TokenKind::Whitespace)
+            .map(|token| token.text.trim_end().to_string())
+            .collect()
+    };
+    let unchanged = content(&tokens) == content(&lexer::tokenize(&formatted));
+
+    (unchanged && parse::Program::parse_from_str(&formatted).is_ok()).then_some(formatted)
+}
+
+/// Format top-level items, which overlap byte range `range`, leaving the rest of the text as is.
+///
+/// Return byte range of each changed item with its formatted text, or `None` if the program can't
+/// be formatted.
+pub fn format_items(
+    text: &str,
+    range: Range<usize>,
+    options: &FormatOptions,
+) -> Option<Vec<(Range<usize>, String)>> {
+    let formatted = format(text, options)?;
+    let original = item_ranges(text);
+    let changed = item_ranges(&formatted);
+    if original.len() != changed.len() {
+        return None;
+    }
+
+    Some(
+        original
+            .into_iter()
+            .zip(changed)
+            .filter(|(item, _)| item.start <= range.end && range.start <= item.end)
+            .filter(|(item, new)| text[item.clone()] != formatted[new.clone()])
+            .map(|(item, new)| (item, formatted[new].to_string()))
+            .collect(),
+    )
+}
+
+/// Re-indent lines of the statement, which ends with `;` or `}` right before byte `offset`.
+///
+/// Indentation is computed from the brackets of the token stream, so the rest of the document
+/// doesn't have to parse. Return byte range of the leading whitespace of each changed line with
+/// its new indentation.
+pub fn reindent_statement(
+    text: &str,
+    offset: usize,
+    options: &FormatOptions,
+) -> Vec<(Range<usize>, String)> {
+    let tokens: Vec<Token> = lexer::tokenize(text)
+        .into_iter()
+        .filter(|token| token.kind != TokenKind::Whitespace)
+        .collect();
+    let Some(end) = tokens
+        .iter()
+        .position(|token| token.end_offset() == offset && (token.is(";") || token.is("}")))
+    else {
+        return Vec::new();
+    };
+    let start = statement_start(text, &tokens, end);
+
+    let printer = Printer::new(*options, HashSet::new());
+    let mut stack = Vec::new();
+    let mut edits = Vec::new();
+    for (index, token) in tokens[..=end].iter().enumerate() {
+        if matches!(token.text, "}" | ")" | "]") {
+            stack.pop();
+        }
+
+        let line_start = text[..token.offset]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        let leading = &text[line_start..token.offset];
+        if index >= start && leading.chars().all(char::is_whitespace) {
+            let blocks = stack
+                .iter()
+                .filter(|bracket| matches!(bracket, Bracket::Block { .. }))
+                .count();
+            let in_group = stack.last() == Some(&Bracket::Group) && !token.is("}");
+            let indentation = printer.indentation(blocks + usize::from(in_group));
+            if leading != indentation {
+                edits.push((line_start..token.offset, indentation));
+            }
+        }
+
+        match token.text {
+            "{" => stack.push(Bracket::Block { is_match: false }),
+            "(" | "[" => stack.push(Bracket::Group),
+            _ => {}
+        }
+    }
+    edits
+}
+
+/// Return index of the first token of the statement, which ends with token `end`.
+///
+/// The statement starts after the `;` or the opening bracket before it, or after a `}` which
+/// ends its line, e.g. of a `match` statement or a top-level item.
+fn statement_start(text: &str, tokens: &[Token], end: usize) -> usize {
+    let mut depth = usize::from(tokens[end].is("}"));
+    for index in (0..end).rev() {
+        let token = &tokens[index];
+        let boundary = match token.text {
+            "}" => tokens
+                .get(index + 1)
+                .is_some_and(|next| text[token.end_offset()..next.offset].contains('\n')),
+            "{" | "(" | "[" | ";" => true,
+            _ => false,
+        };
+        if boundary && depth == 0 {
+            return index + 1;
+        }
+        match token.text {
+            "}" | ")" | "]" => depth += 1,
+            "{" | "(" | "[" => depth -= 1,
+            _ => {}
+        }
+    }
+    0
+}
+
+/// Return byte offsets of the opening braces of `match` bodies in the functions of the program.
+fn match_bodies(program: &parse::Program, text: &str) -> HashSet<usize> {
+    let rope = Rope::from_str(text);
+    let mut bodies = HashSet::new();
+
+    for item in program.items() {
+        let parse::Item::Function(func) = item else {
+            continue;
+        };
+        for expr in parse::ExprTree::Expression(func.body()).pre_order_iter() {
+            let parse::ExprTree::Match(match_) = expr else {
+                continue;
+            };
+            let (start, end) = span_to_positions(&rope, match_.as_ref());
+            let start = position_to_byte(&rope, start);
+            let end = position_to_byte(&rope, end);
+
+            // The body is closed by the last brace of the `match` expression.
+            let tokens = lexer::significant(&lexer::tokenize(&text[start..end]));
+            let open = tokens
+                .iter()
+                .rposition(|token| token.is("}"))
+                .and_then(|close| scope::matching_open(&tokens, close));
+            bodies.extend(open.map(|open| start + tokens[open].offset));
+        }
+    }
+    bodies
+}
+
+/// Return byte ranges of top-level items, from their first token to the closing `}` or `;`.
+fn item_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+
+    for token in lexer::significant(&lexer::tokenize(text)) {
+        let item_start = *start.get_or_insert(token.offset);
+        match token.text {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth == 0 && (token.is("}") || token.is(";")) {
+            ranges.push(item_start..token.end_offset());
+            start = None;
+        }
+    }
+
+    ranges
+}
+
+/// Token or comment of a line together with the space before it.
+#[derive(Debug, Clone)]
+struct Piece {
+    text: String,
+    space: bool,
+}
+
+/// Line of formatted output.
+#[derive(Debug, Default)]
+struct Line {
+    indent: usize,
+    pieces: Vec<Piece>,
+    /// Comment at the end of the line.
+    comment: Option<String>,
+    /// Line is preceded by an empty line.
+    blank_before: bool,
+}
+
+/// Bracket, which is open at the current token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bracket {
+    /// Braces, which contents are written on separate lines.
+    Block { is_match: bool },
+    /// Parentheses or square brackets, which contents are written on one line.
+    Group,
+}
+
+/// Printer, which lays out tokens line by line.
+struct Printer {
+    options: FormatOptions,
+    /// Byte offsets of the opening braces of `match` bodies, see [`match_bodies`].
+    match_bodies: HashSet<usize>,
+    lines: Vec<Line>,
+    current: Line,
+    stack: Vec<Bracket>,
+    /// Line ends before the next token, unless it is a closing bracket or a separator.
+    pending_break: bool,
+    /// Next line is preceded by an empty line.
+    pending_blank: bool,
+}
+
+impl Printer {
+    fn new(options: FormatOptions, match_bodies: HashSet<usize>) -> Self {
+        Self {
+            options,
+            match_bodies,
+            lines: Vec::new(),
+            current: Line::default(),
+            stack: Vec::new(),
+            pending_break: false,
+            pending_blank: false,
+        }
+    }
+
+    /// Lay out tokens and render formatted text.
+    fn print(mut self, tokens: &[Token]) -> String {
+        let mut items = Vec::new();
+        let mut newlines = 0;
+        for token in tokens {
+            if token.kind == TokenKind::Whitespace {
+                newlines += token.text.matches('\n').count();
+            } else {
+                items.push((*token, newlines));
+                newlines = 0;
+            }
+        }
+
+        let mut index = 0;
+        while let Some(&(token, newlines)) = items.get(index) {
+            index += 1;
+            match token.kind {
+                TokenKind::Comment | TokenKind::DocComment => self.comment(token.text, newlines),
+                _ if token.is("{") && items.get(index).is_some_and(|(next, _)| next.is("}")) => {
+                    // Empty block stays on one line, e.g. `fn main() {}`.
+                    self.push("{", newlines);
+                    self.push("}", 0);
+                    self.close_block();
+                    index += 1;
+                }
+                _ => self.token(&token, newlines),
+            }
+        }
+        self.end_line();
+
+        self.render()
+    }
+
+    /// Lay out significant token.
+    fn token(&mut self, token: &Token, newlines: usize) {
+        let text = token.text;
+        match text {
+            "{" => {
+                self.push(text, newlines);
+                let is_match = self.match_bodies.contains(&token.offset);
+                self.stack.push(Bracket::Block { is_match });
+                self.pending_break = true;
+            }
+            "}" => {
+                self.end_line();
+                self.stack.pop();
+                self.push(text, newlines);
+                self.close_block();
+            }
+            "(" | "[" => {
+                self.push(text, newlines);
+                self.stack.push(Bracket::Group);
+            }
+            ")" | "]" => {
+                self.stack.pop();
+                self.push(text, newlines);
+            }
+            ";" => {
+                self.push(text, newlines);
+                if self.stack.last() != Some(&Bracket::Group) {
+                    self.pending_break = true;
+                }
+            }
+            "," => {
+                self.push(text, newlines);
+                if self.stack.last() == Some(&Bracket::Block { is_match: true }) {
+                    self.pending_break = true;
+                }
+            }
+            _ => self.push(text, newlines),
+        }
+    }
+
+    /// Finish the line after closing brace, separating top-level items by an empty line.
+    fn close_block(&mut self) {
+        self.pending_break = true;
+        if self.stack.is_empty() {
+            self.pending_blank = true;
+        }
+    }
+
+    /// Lay out comment, keeping it at the end of the line if it was there in the source.
+    fn comment(&mut self, text: &str, newlines: usize) {
+        let text = text.trim_end();
+        if newlines == 0 && !self.current.pieces.is_empty() {
+            if text.starts_with("/*") && !self.pending_break {
+                self.current.pieces.push(Piece {
+                    text: text.to_string(),
+                    space: true,
+                });
+            } else {
+                self.current.comment = Some(text.to_string());
+                self.end_line();
+            }
+            return;
+        }
+
+        self.end_line();
+        self.start_line(newlines, false);
+        self.current.comment = Some(text.to_string());
+        self.end_line();
+    }
+
+    /// Append token to the current line, starting a new one if needed.
+    fn push(&mut self, text: &str, newlines: usize) {
+        if self.pending_break && !matches!(text, "," | ";" | ")" | "]") {
+            self.end_line();
+        }
+        self.pending_break = false;
+
+        let space = if let Some(previous) = self.current.pieces.last() {
+            needs_space(&previous.text, text)
+        } else {
+            self.start_line(newlines, text == "}");
+            false
+        };
+        self.current.pieces.push(Piece {
+            text: text.to_string(),
+            space,
+        });
+    }
+
+    /// Set indentation and the preceding empty line of the current line.
+    fn start_line(&mut self, newlines: usize, closing: bool) {
+        let blocks = self
+            .stack
+            .iter()
+            .filter(|bracket| matches!(bracket, Bracket::Block { .. }))
+            .count();
+        let in_group = self.stack.last() == Some(&Bracket::Group);
+        let after_open = self.lines.last().is_none_or(|line| {
+            line.comment.is_none() && line.pieces.last().is_some_and(|piece| piece.text == "{")
+        });
+
+        self.current.indent = blocks + usize::from(in_group && !closing);
+        self.current.blank_before = !self.lines.is_empty()
+            && !closing
+            && (self.pending_blank || (newlines >= 2 && !in_group && !after_open));
+        self.pending_blank = false;
+    }
+
+    /// Move the current line to the output, if it is not empty.
+    fn end_line(&mut self) {
+        if !self.current.pieces.is_empty() || self.current.comment.is_some() {
+            self.lines.push(std::mem::take(&mut self.current));
+        }
+        self.pending_break = false;
+    }
+
+    /// Render lines, breaking the ones which are too long.
+    fn render(&self) -> String {
+        let mut lines = Vec::new();
+        for line in &self.lines {
+            self.break_line(
+                Line {
+                    indent: line.indent,
+                    pieces: line.pieces.clone(),
+                    comment: line.comment.clone(),
+                    blank_before: line.blank_before,
+                },
+                &mut lines,
+            );
+        }
+
+        let mut output = String::new();
+        for line in lines {
+            if line.blank_before {
+                output.push('\n');
+            }
+            output.push_str(&self.indentation(line.indent));
+            output.push_str(&join(&line.pieces));
+            if let Some(comment) = &line.comment {
+                if !line.pieces.is_empty() {
+                    output.push(' ');
+                }
+                output.push_str(comment);
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Return indentation of the given level.
+    fn indentation(&self, level: usize) -> String {
+        if self.options.use_tabs {
+            "\t".repeat(level)
+        } else {
+            " ".repeat(level * self.options.indent_width)
+        }
+    }
+
+    /// Return width of the rendered line.
+    fn width(&self, line: &Line) -> usize {
+        line.indent * self.options.indent_width
+            + join(&line.pieces).chars().count()
+            + line
+                .comment
+                .as_ref()
+                .map_or(0, |comment| comment.chars().count() + 1)
+    }
+
+    /// Break the line, which is too long, putting elements of its last argument list on separate
+    /// lines.
+    fn break_line(&self, line: Line, output: &mut Vec<Line>) {
+        let group = (self.width(&line) > self.options.max_width)
+            .then(|| last_group(&line.pieces))
+            .flatten();
+        let Some((open, close)) = group else {
+            output.push(line);
+            return;
+        };
+
+        output.push(Line {
+            indent: line.indent,
+            pieces: line.pieces[..=open].to_vec(),
+            comment: None,
+            blank_before: line.blank_before,
+        });
+        for element in split_elements(&line.pieces[open + 1..close]) {
+            let mut pieces = element.to_vec();
+            pieces[0].space = false;
+            self.break_line(
+                Line {
+                    indent: line.indent + 1,
+                    pieces,
+                    comment: None,
+                    blank_before: false,
+                },
+                output,
+            );
+        }
+        let mut pieces = line.pieces[close..].to_vec();
+        pieces[0].space = false;
+        self.break_line(
+            Line {
+                indent: line.indent,
+                pieces,
+                comment: line.comment,
+                blank_before: false,
+            },
+            output,
+        );
+    }
+}
+
+/// Join pieces of the line with their spaces.
+fn join(pieces: &[Piece]) -> String {
+    let mut text = String::new();
+    for (index, piece) in pieces.iter().enumerate() {
+        if index > 0 && piece.space {
+            text.push(' ');
+        }
+        text.push_str(&piece.text);
+    }
+    text
+}
+
+/// Return indices of the brackets of the last argument list, which is not nested in other
+/// brackets of the line. Only lists with several elements or nested calls are broken, so e.g.
+/// array types stay on one line.
+fn last_group(pieces: &[Piece]) -> Option<(usize, usize)> {
+    let breakable = |open: usize, close: usize| {
+        pieces[open].text != "{"
+            && (split_elements(&pieces[open + 1..close]).len() > 1
+                || pieces[open + 1..close]
+                    .iter()
+                    .any(|piece| piece.text == "("))
+    };
+
+    let mut depth = 0usize;
+    let mut open = None;
+    let mut group = None;
+
+    for (index, piece) in pieces.iter().enumerate() {
+        match piece.text.as_str() {
+            "(" | "[" | "{" => {
+                if depth == 0 {
+                    open = Some(index);
+                }
+                depth += 1;
+            }
+            ")" | "]" | "}" => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    group = open
+                        .filter(|open| breakable(*open, index))
+                        .map(|open| (open, index))
+                        .or(group);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    group
+}
+
+/// Split pieces at commas, which are not nested in brackets, keeping each comma with its element.
+fn split_elements(pieces: &[Piece]) -> Vec<&[Piece]> {
+    let mut elements = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (index, piece) in pieces.iter().enumerate() {
+        match piece.text.as_str() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth = depth.saturating_sub(1),
+            "," if depth == 0 => {
+                elements.push(&pieces[start..=index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < pieces.len() {
+        elements.push(&pieces[start..]);
+    }
+    elements
+}
+
+/// Check if the tokens are separated by a space.
+fn needs_space(previous: &str, next: &str) -> bool {
+    let is_word = |text: &str| {
+        text.starts_with(|ch: char| ch.is_alphanumeric() || ch == '_') && !KEYWORDS.contains(&text)
+    };
+
+    if matches!(previous, "(" | "[" | "<" | "::" | "!") || (previous, next) == ("{", "}") {
+        return false;
+    }
+    match next {
+        ")" | "]" | ">" | "," | ";" | ":" | "::" | "!" => false,
+        "(" => !is_word(previous) && previous != ">",
+        "<" => !is_word(previous),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_default(text: &str) -> String {
+        format(text, &FormatOptions::default()).expect("Expected formattable program")
+    }
+
+    #[test]
+    fn test_format() {
+        let text = "/// Entry point\nfn main( ) {let x : u32=jet::add_32( 1,2 ) ; // sum\n\n\n  match jet::is_zero_32(x){true=>{ assert!(false)},false=>()}}";
+        assert_eq!(
+            format_default(text),
+            "/// Entry point
+fn main() {
+    let x: u32 = jet::add_32(1, 2); // sum
+
+    match jet::is_zero_32(x) {
+        true => {
+            assert!(false)
+        },
+        false => ()
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let text = include_str!("../examples/recursive_covenant.simf");
+        let formatted = format_default(text);
+        assert_eq!(format_default(&formatted), formatted);
+        assert!(formatted.contains("fn recursive_covenant() {\n"));
+    }
+
+    #[test]
+    fn test_format_breaks_long_lines() {
+        let options = FormatOptions {
+            max_width: 40,
+            ..FormatOptions::default()
+        };
+        let text = "fn f(a: u32, b: u32) -> u32 { a }\nfn main() { let x: u32 = f(0x00000001, 0x00000002); }";
+        let formatted = format(text, &options).expect("Expected formattable program");
+        assert!(
+            formatted
+                .contains("    let x: u32 = f(\n        0x00000001,\n        0x00000002\n    );\n")
+        );
+        assert_eq!(format(&formatted, &options), Some(formatted));
+    }
+
+    #[test]
+    fn test_format_items() {
+        let text = "fn f( ) {}\n\nfn main() {\nlet x: u8 = 1;\n}\n";
+        let second = text.find("let").unwrap();
+        let edits = format_items(text, second..second, &FormatOptions::default())
+            .expect("Expected formattable program");
+
+        assert_eq!(edits.len(), 1);
+        assert_eq!(&text[edits[0].0.clone()], "fn main() {\nlet x: u8 = 1;\n}");
+        assert_eq!(edits[0].1, "fn main() {\n    let x: u8 = 1;\n}");
+    }
+
+    #[test]
+    fn test_reindent_statement() {
+        let text =
+            "fn main() {\nlet x: u8 = f(\n1,\n  2\n);\n    match x {\n0 => (),\n  }\n  let y";
+        let offset = text.find(");").unwrap() + 2;
+        let edits = reindent_statement(text, offset, &FormatOptions::default());
+        let lines: Vec<(&str, &str)> = edits
+            .iter()
+            .map(|(range, indentation)| (&text[range.clone()], indentation.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("", "    "),
+                ("", "        "),
+                ("  ", "        "),
+                ("", "    ")
+            ]
+        );
+
+        let offset = text.find("  }").unwrap() + 3;
+        let edits = reindent_statement(text, offset, &FormatOptions::default());
+        assert_eq!(edits.len(), 2);
+        assert_eq!(&text[edits[0].0.end..edits[0].0.end + 4], "0 =>");
+        assert_eq!(edits[0].1, "        ");
+        assert_eq!(edits[1].1, "    ");
+    }
+
+    #[test]
+    fn test_format_invalid_program() {
+        assert_eq!(format("fn main( {", &FormatOptions::default()), None);
+    }
+}
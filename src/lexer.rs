@@ -0,0 +1,190 @@
+use tower_lsp_server::lsp_types::Position;
+
+/// Kind of lexical token in `SimplicityHL` source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Identifier or keyword.
+    Ident,
+    /// Decimal, hexadecimal or binary literal.
+    Number,
+    /// `///` documentation comment.
+    DocComment,
+    /// `//` or `/* */` comment.
+    Comment,
+    /// Punctuation, where `::`, `->` and `=>` are single tokens.
+    Punct,
+    /// Sequence of whitespace characters, including newlines.
+    Whitespace,
+}
+
+/// Lexical token with its location in source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte offset of the token in source.
+    pub offset: usize,
+    /// Zero-based position of the first character, whose column counts UTF-16 code units.
+    pub start: Position,
+    /// Zero-based position after the last character.
+    pub end: Position,
+}
+
+impl Token<'_> {
+    /// Return `true` for whitespace and comments.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Whitespace | TokenKind::Comment | TokenKind::DocComment
+        )
+    }
+
+    /// Return `true` if token has given text.
+    pub fn is(&self, text: &str) -> bool {
+        self.text == text
+    }
+
+    /// Byte offset right after the token.
+    pub fn end_offset(&self) -> usize {
+        self.offset + self.text.len()
+    }
+}
+
+/// Split `SimplicityHL` source into tokens, keeping whitespace and comments.
+///
+/// Lexing never fails: unknown characters become [`TokenKind::Punct`], so the result can be used
+/// on files which can't be parsed.
+pub fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut position = Position::new(0, 0);
+    let mut offset = 0;
+
+    while offset < text.len() {
+        let rest = &text[offset..];
+        let (kind, len) = next_token(rest);
+        let token_text = &rest[..len];
+
+        let start = position;
+        for ch in token_text.chars() {
+            if ch == '\n' {
+                position.line += 1;
+                position.character = 0;
+            } else {
+                position.character += u32::try_from(ch.len_utf16()).unwrap_or_default();
+            }
+        }
+
+        tokens.push(Token {
+            kind,
+            text: token_text,
+            offset,
+            start,
+            end: position,
+        });
+        offset += len;
+    }
+
+    tokens
+}
+
+/// Return tokens without whitespace and comments.
+pub fn significant<'a>(tokens: &[Token<'a>]) -> Vec<Token<'a>> {
+    tokens
+        .iter()
+        .filter(|token| !token.is_trivia())
+        .copied()
+        .collect()
+}
+
+/// Return kind and byte length of the token at the start of `rest`.
+fn next_token(rest: &str) -> (TokenKind, usize) {
+    let Some(first) = rest.chars().next() else {
+        return (TokenKind::Whitespace, 0);
+    };
+
+    if first.is_whitespace() {
+        return (TokenKind::Whitespace, take_while(rest, char::is_whitespace));
+    }
+
+    if rest.starts_with("//") {
+        let len = rest.find('\n').unwrap_or(rest.len());
+        let kind = if rest.starts_with("///") && !rest.starts_with("////") {
+            TokenKind::DocComment
+        } else {
+            TokenKind::Comment
+        };
+        return (kind, len);
+    }
+
+    if let Some(body) = rest.strip_prefix("/*") {
+        let len = body.find("*/").map_or(rest.len(), |end| end + 4);
+        return (TokenKind::Comment, len);
+    }
+
+    if first.is_ascii_digit() {
+        return (
+            TokenKind::Number,
+            take_while(rest, |ch| ch.is_ascii_alphanumeric() || ch == '_'),
+        );
+    }
+
+    if first.is_alphabetic() || first == '_' {
+        return (
+            TokenKind::Ident,
+            take_while(rest, |ch| ch.is_alphanumeric() || ch == '_'),
+        );
+    }
+
+    if ["::", "->", "=>"]
+        .iter()
+        .any(|punct| rest.starts_with(punct))
+    {
+        return (TokenKind::Punct, 2);
+    }
+
+    (TokenKind::Punct, first.len_utf8())
+}
+
+/// Return byte length of the longest prefix which characters satisfy `predicate`.
+fn take_while(rest: &str, predicate: impl Fn(char) -> bool) -> usize {
+    rest.find(|ch| !predicate(ch)).unwrap_or(rest.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(text: &str) -> Vec<&str> {
+        significant(&tokenize(text))
+            .iter()
+            .map(|token| token.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            texts("fn f(a: u8) -> u8 { jet::add_8(a, 0x1f) } // done"),
+            vec![
+                "fn", "f", "(", "a", ":", "u8", ")", "->", "u8", "{", "jet", "::", "add_8", "(",
+                "a", ",", "0x1f", ")", "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let tokens = tokenize("/// doc\nfn main() {}");
+        assert_eq!(tokens[0].kind, TokenKind::DocComment);
+
+        let main = tokens.iter().find(|token| token.is("main")).unwrap();
+        assert_eq!(main.start, Position::new(1, 3));
+        assert_eq!(main.end, Position::new(1, 7));
+        assert_eq!(main.offset, 11);
+
+        // Characters outside the basic plane take two UTF-16 code units.
+        let tokens = tokenize("/* \u{1d53d} */ x");
+        let x = tokens.iter().find(|token| token.is("x")).unwrap();
+        assert_eq!(x.start, Position::new(0, 9));
+    }
+}
@@ -0,0 +1,51 @@
+use ropey::Rope;
+use tower_lsp_server::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+use crate::function::Functions;
+use crate::utils::find_function_name_range;
+
+/// Code of the diagnostic reported for functions without documentation.
+pub const MISSING_DOCS: &str = "missing_docs";
+
+/// Return warnings at the names of functions other than `main`, which have no `///` comments.
+pub fn missing_docs(functions: &Functions, rope: &Rope) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = functions
+        .functions_and_docs()
+        .into_iter()
+        .filter(|(func, doc)| doc.is_empty() && func.name().as_inner() != "main")
+        .filter_map(|(func, _)| {
+            let range = find_function_name_range(func, rope).ok()?;
+            Some(Diagnostic {
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(MISSING_DOCS.to_string())),
+                ..Diagnostic::new_simple(
+                    range,
+                    format!("Function `{}` has no documentation", func.name()),
+                )
+            })
+        })
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::functions_from;
+
+    #[test]
+    fn test_missing_docs() {
+        let text = "/// Documented.\nfn documented() {}\nfn undocumented() {}\nfn main() {}";
+        let functions = functions_from(text);
+        let rope = Rope::from_str(text);
+
+        let diagnostics = missing_docs(&functions, &rope);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Function `undocumented` has no documentation"
+        );
+        assert_eq!(diagnostics[0].range.start.line, 2);
+    }
+}
@@ -1,9 +1,20 @@
 #![warn(clippy::all, clippy::pedantic)]
 
 mod backend;
+mod code_action;
 mod completion;
 mod error;
+mod folding;
+mod format;
 mod function;
+mod lexer;
+mod lint;
+mod scope;
+mod selection;
+mod semantic;
+#[cfg(test)]
+mod test_utils;
+mod type_utils;
 mod utils;
 
 use backend::Backend;
@@ -0,0 +1,449 @@
+use tower_lsp_server::lsp_types::Position;
+
+use crate::lexer::{self, Token, TokenKind};
+use crate::type_utils::{self, Aliases};
+
+/// Kind of the name visible at the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    /// Parameter of the enclosing function.
+    Parameter,
+    /// Variable bound by `let` or by a `match` arm.
+    Variable,
+    /// Constant declared in `mod witness`.
+    Witness,
+    /// Constant declared in `mod param`.
+    Param,
+}
+
+/// Name bound in the document together with its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub name: String,
+    /// Type of the binding, empty when it can't be derived from the annotation.
+    pub ty: String,
+    pub kind: BindingKind,
+    /// Position of the name in the declaration.
+    pub position: Position,
+}
+
+/// Binding together with the nesting depth of the scope it belongs to.
+struct ScopedBinding {
+    binding: Binding,
+    depth: usize,
+    /// Bindings of `match` arms go out of scope on the `,` ending the arm.
+    arm: bool,
+}
+
+/// Return parameters and variables of the enclosing function visible at the cursor.
+///
+/// Later bindings shadow earlier ones with the same name.
+pub fn bindings_at(text: &str, position: Position, aliases: &Aliases) -> Vec<Binding> {
+    let tokens: Vec<Token> = lexer::significant(&lexer::tokenize(text))
+        .into_iter()
+        .filter(|token| token.end <= position)
+        .collect();
+
+    let Some(fn_index) = enclosing_function(&tokens) else {
+        return vec![];
+    };
+    let tokens = &tokens[fn_index..];
+
+    let Some(params_end) = matching_close(tokens, 2) else {
+        return vec![];
+    };
+    let mut scoped: Vec<ScopedBinding> = parameters(text, &tokens[3..params_end])
+        .into_iter()
+        .map(|binding| ScopedBinding {
+            binding,
+            depth: 0,
+            arm: false,
+        })
+        .collect();
+
+    let Some(body) = tokens
+        .iter()
+        .skip(params_end)
+        .position(|token| token.is("{"))
+        .map(|index| index + params_end)
+    else {
+        return vec![];
+    };
+
+    // Bindings of `let` statements become visible after the `;` ending the statement.
+    let mut pending: Vec<ScopedBinding> = Vec::new();
+    let mut depth = 1usize;
+
+    for (index, token) in tokens.iter().enumerate().skip(body + 1) {
+        match token.text {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => {
+                depth = depth.saturating_sub(1);
+                scoped.retain(|item| item.depth <= depth);
+                pending.retain(|item| item.depth <= depth);
+            }
+            ";" => {
+                let (visible, rest): (Vec<_>, Vec<_>) =
+                    pending.into_iter().partition(|item| item.depth == depth);
+                scoped.extend(visible);
+                pending = rest;
+            }
+            "," => scoped.retain(|item| !(item.arm && item.depth == depth)),
+            "let" => pending.extend(
+                let_bindings(text, &tokens[index..], aliases)
+                    .into_iter()
+                    .map(|binding| ScopedBinding {
+                        binding,
+                        depth,
+                        arm: false,
+                    }),
+            ),
+            "=>" => {
+                scoped.extend(
+                    arm_binding(text, &tokens[..index]).map(|binding| ScopedBinding {
+                        binding,
+                        depth,
+                        arm: true,
+                    }),
+                )
+            }
+            _ => {}
+        }
+    }
+
+    let mut bindings: Vec<Binding> = Vec::new();
+    for ScopedBinding { binding, .. } in scoped {
+        bindings.retain(|item| item.name != binding.name);
+        bindings.push(binding);
+    }
+    bindings
+}
+
+/// Return constants declared in `mod <module> { const NAME: TYPE = VALUE; }`.
+pub fn module_constants(text: &str, module: &str) -> Vec<Binding> {
+    let tokens = lexer::significant(&lexer::tokenize(text));
+    let kind = if module == "witness" {
+        BindingKind::Witness
+    } else {
+        BindingKind::Param
+    };
+
+    let Some(start) = tokens
+        .windows(3)
+        .position(|window| window[0].is("mod") && window[1].is(module) && window[2].is("{"))
+    else {
+        return vec![];
+    };
+    let end = matching_close(&tokens, start + 2).unwrap_or(tokens.len());
+    let body = &tokens[start + 3..end];
+
+    body.iter()
+        .enumerate()
+        .filter(|(_, token)| token.is("const"))
+        .filter_map(|(index, _)| {
+            let [name, colon, ..] = body.get(index + 1..)? else {
+                return None;
+            };
+            if !colon.is(":") {
+                return None;
+            }
+            let equals = body[index..].iter().find(|token| token.is("="))?;
+            Some(Binding {
+                name: name.text.to_string(),
+                ty: text_between(text, colon, equals),
+                kind,
+                position: name.start,
+            })
+        })
+        .collect()
+}
+
+/// Return index of the `fn` keyword of the function enclosing the end of `tokens`.
+fn enclosing_function(tokens: &[Token]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut function = None;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => depth = depth.saturating_sub(1),
+            "fn" if depth == 0 => function = Some(index),
+            _ => {}
+        }
+    }
+
+    function.filter(|_| depth > 0)
+}
+
+/// Return index of the bracket closing the one at `open`.
+pub fn matching_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match token.text {
+            "{" | "(" | "[" => depth += 1,
+            "}" | ")" | "]" => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Return source text between two tokens, without the tokens themselves.
+fn text_between(text: &str, start: &Token, end: &Token) -> String {
+    text.get(start.end_offset()..end.offset)
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Parse function parameters `name: Type, ...` from tokens inside the parentheses.
+fn parameters(text: &str, tokens: &[Token]) -> Vec<Binding> {
+    split_at_commas(tokens)
+        .into_iter()
+        .filter_map(|param| match param {
+            [name, colon, .., last] if colon.is(":") => Some(Binding {
+                name: name.text.to_string(),
+                ty: text
+                    .get(colon.end_offset()..last.end_offset())
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+                kind: BindingKind::Parameter,
+                position: name.start,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split tokens at commas, which are not nested in brackets.
+pub fn split_at_commas<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<&'b [Token<'a>]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.text {
+            "(" | "[" | "<" => depth += 1,
+            ")" | "]" | ">" => depth = depth.saturating_sub(1),
+            "," if depth == 0 => {
+                parts.push(&tokens[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+/// Return bindings of the `let` statement which starts `tokens`.
+fn let_bindings(text: &str, tokens: &[Token], aliases: &Aliases) -> Vec<Binding> {
+    let Some(colon) = tokens.iter().position(|token| token.is(":")) else {
+        return vec![];
+    };
+    let Some(equals) = tokens.iter().position(|token| token.is("=")) else {
+        return vec![];
+    };
+    if equals < colon {
+        return vec![];
+    }
+
+    let mut index = 1;
+    let Some(pattern) = Pattern::parse(&tokens[..colon], &mut index) else {
+        return vec![];
+    };
+
+    let mut bindings = Vec::new();
+    pattern.bind(
+        &text_between(text, &tokens[colon], &tokens[equals]),
+        aliases,
+        &mut bindings,
+    );
+    bindings
+}
+
+/// Return binding of the `match` arm, which `=>` ends `tokens`, e.g. `Left(x: u8) =>`.
+fn arm_binding(text: &str, tokens: &[Token]) -> Option<Binding> {
+    let close = tokens.len().checked_sub(1)?;
+    let open = matching_open(tokens, close)?;
+    let variant = tokens.get(open.checked_sub(1)?)?;
+    if !matches!(variant.text, "Left" | "Right" | "Some") {
+        return None;
+    }
+
+    match &tokens[open + 1..close] {
+        [name, colon, _, ..] if name.kind == TokenKind::Ident && colon.is(":") => Some(Binding {
+            name: name.text.to_string(),
+            ty: text_between(text, colon, &tokens[close]),
+            kind: BindingKind::Variable,
+            position: name.start,
+        }),
+        _ => None,
+    }
+}
+
+/// Return index of the bracket opening the one at `close`.
+pub fn matching_open(tokens: &[Token], close: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for index in (0..=close).rev() {
+        match tokens[index].text {
+            "}" | ")" | "]" => depth += 1,
+            "{" | "(" | "[" => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Pattern on the left side of `let`.
+enum Pattern {
+    Identifier(String, Position),
+    Ignore,
+    Tuple(Vec<Pattern>),
+    Array(Vec<Pattern>),
+}
+
+impl Pattern {
+    /// Parse pattern starting at `tokens[*index]`, advancing `index` past it.
+    fn parse(tokens: &[Token], index: &mut usize) -> Option<Self> {
+        let token = tokens.get(*index)?;
+        *index += 1;
+
+        match token.text {
+            "_" => Some(Pattern::Ignore),
+            "(" | "[" => {
+                let close = if token.is("(") { ")" } else { "]" };
+                let mut elements = Vec::new();
+                while !tokens.get(*index)?.is(close) {
+                    elements.push(Pattern::parse(tokens, index)?);
+                    if tokens.get(*index)?.is(",") {
+                        *index += 1;
+                    }
+                }
+                *index += 1;
+
+                Some(if close == ")" {
+                    Pattern::Tuple(elements)
+                } else {
+                    Pattern::Array(elements)
+                })
+            }
+            _ if token.kind == TokenKind::Ident => {
+                Some(Pattern::Identifier(token.text.to_string(), token.start))
+            }
+            _ => None,
+        }
+    }
+
+    /// Bind identifiers of the pattern to the matching parts of type `ty`.
+    fn bind(&self, ty: &str, aliases: &Aliases, bindings: &mut Vec<Binding>) {
+        match self {
+            Pattern::Identifier(name, position) => bindings.push(Binding {
+                name: name.clone(),
+                ty: ty.to_string(),
+                kind: BindingKind::Variable,
+                position: *position,
+            }),
+            Pattern::Ignore => {}
+            Pattern::Tuple(elements) => {
+                let types = type_utils::tuple_elements(ty)
+                    .or_else(|| type_utils::tuple_elements(&type_utils::resolve_alias(ty, aliases)))
+                    .unwrap_or_default();
+                for (index, element) in elements.iter().enumerate() {
+                    element.bind(
+                        types.get(index).map_or("", String::as_str),
+                        aliases,
+                        bindings,
+                    );
+                }
+            }
+            Pattern::Array(elements) => {
+                let element_type = type_utils::array_element(ty)
+                    .or_else(|| type_utils::array_element(&type_utils::resolve_alias(ty, aliases)))
+                    .map(|(element, _)| element)
+                    .unwrap_or_default();
+                for element in elements {
+                    element.bind(&element_type, aliases, bindings);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names_and_types(bindings: &[Binding]) -> Vec<(&str, &str)> {
+        bindings
+            .iter()
+            .map(|binding| (binding.name.as_str(), binding.ty.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_bindings_at() {
+        let text = "fn f(a: u8, b: (u8, u16)) -> u8 {
+    let (carry, sum): (bool, u8) = jet::add_8(a, a);
+    let x: u8 = match jet::is_zero_8(sum) {
+        true => { let hidden: u8 = 0; hidden },
+        false => a,
+    };
+    let pending: u8 = ";
+
+        let end = Position::new(6, 22);
+        let bindings = bindings_at(text, end, &Aliases::new());
+        assert_eq!(
+            names_and_types(&bindings),
+            vec![
+                ("a", "u8"),
+                ("b", "(u8, u16)"),
+                ("carry", "bool"),
+                ("sum", "u8"),
+                ("x", "u8")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arm_bindings() {
+        let text = "fn f(e: Either<u8, u16>) {
+    match e {
+        Left(l: u8) => jet::is_zero_8(l),
+        Right(r: u16) => ";
+
+        let bindings = bindings_at(text, Position::new(3, 25), &Aliases::new());
+        assert_eq!(
+            names_and_types(&bindings),
+            vec![("e", "Either<u8, u16>"), ("r", "u16")]
+        );
+    }
+
+    #[test]
+    fn test_module_constants() {
+        let text = "mod witness { const SIG: Signature = 0x00; }
+mod param { const KEY: u256 = 0x01; const OTHER: [u8; 2] = [1, 2]; }";
+
+        assert_eq!(
+            names_and_types(&module_constants(text, "witness")),
+            vec![("SIG", "Signature")]
+        );
+        assert_eq!(
+            names_and_types(&module_constants(text, "param")),
+            vec![("KEY", "u256"), ("OTHER", "[u8; 2]")]
+        );
+    }
+}
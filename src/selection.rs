@@ -0,0 +1,123 @@
+use miniscript::iter::TreeLike;
+use ropey::Rope;
+use simplicityhl::error::Span;
+use simplicityhl::parse;
+use tower_lsp_server::lsp_types::{Position, Range, SelectionRange};
+
+use crate::function::Functions;
+use crate::lexer;
+use crate::utils::{document_end, position_to_span, span_contains, span_to_positions};
+
+/// Return selection range at `position`, which grows from the token to the enclosing nodes of
+/// the expression tree, the function and the whole document.
+pub fn selection_range(rope: &Rope, functions: &Functions, position: Position) -> SelectionRange {
+    let text = rope.to_string();
+    let tokens = lexer::significant(&lexer::tokenize(&text));
+    // Token starting at the cursor is preferred to the token ending there.
+    let token = tokens
+        .iter()
+        .find(|token| token.start <= position && position < token.end)
+        .or_else(|| tokens.iter().find(|token| token.end == position));
+
+    let mut ranges = vec![Range::new(Position::new(0, 0), document_end(rope))];
+    if let Ok(span) = position_to_span(rope, position) {
+        ranges.extend(
+            node_spans(functions, &span)
+                .iter()
+                .map(|span| span_to_positions(rope, span))
+                .map(|(start, end)| Range::new(start, end)),
+        );
+    }
+    ranges.extend(token.map(|token| Range::new(token.start, token.end)));
+
+    // Enclosing ranges go first, so every range is the parent of the next one.
+    ranges.sort_by_key(|range| (range.start, std::cmp::Reverse(range.end)));
+    ranges.dedup();
+
+    let mut selection: Option<SelectionRange> = None;
+    for range in ranges {
+        // Spans of outdated functions may not enclose the cursor.
+        if !(range.start <= position && position <= range.end) {
+            continue;
+        }
+        if selection
+            .as_ref()
+            .is_some_and(|parent| range.start < parent.range.start || parent.range.end < range.end)
+        {
+            continue;
+        }
+        selection = Some(SelectionRange {
+            range,
+            parent: selection.map(Box::new),
+        });
+    }
+    selection.unwrap_or_else(|| SelectionRange {
+        range: Range::new(position, position),
+        parent: None,
+    })
+}
+
+/// Return spans of the function containing `span` and of its expression tree nodes containing
+/// `span`: expressions, calls, statements and `match` expressions.
+fn node_spans(functions: &Functions, span: &Span) -> Vec<Span> {
+    let Some(func) = functions
+        .functions()
+        .into_iter()
+        .find(|func| span_contains(func.span(), span))
+    else {
+        return vec![];
+    };
+
+    let mut spans = vec![*func.span()];
+    spans.extend(
+        parse::ExprTree::Expression(func.body())
+            .pre_order_iter()
+            .filter_map(|expr| match expr {
+                parse::ExprTree::Expression(expression) => Some(*expression.as_ref()),
+                parse::ExprTree::Single(single) => Some(*single.as_ref()),
+                parse::ExprTree::Call(call) => Some(*call.span()),
+                parse::ExprTree::Assignment(assignment) => Some(*assignment.as_ref()),
+                parse::ExprTree::Match(match_) => Some(*match_.as_ref()),
+                _ => None,
+            })
+            .filter(|node| span_contains(node, span)),
+    );
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::functions_from;
+
+    #[test]
+    fn test_selection_range() {
+        let text = "fn add(a: u32, b: u32) -> u32 {
+    let (_, sum): (bool, u32) = jet::add_32(a, b);
+    sum
+}
+
+fn main() {}";
+        let functions = functions_from(text);
+        let rope = Rope::from_str(text);
+
+        let mut selected = Vec::new();
+        let mut selection = Some(selection_range(&rope, &functions, Position::new(1, 47)));
+        while let Some(current) = selection {
+            let start = crate::utils::position_to_byte(&rope, current.range.start);
+            let end = crate::utils::position_to_byte(&rope, current.range.end);
+            selected.push(text[start..end].to_string());
+            selection = current.parent.map(|parent| *parent);
+        }
+
+        assert_eq!(selected.first().map(String::as_str), Some("b"));
+        assert!(selected.iter().any(|text| text == "jet::add_32(a, b)"));
+        assert!(
+            selected
+                .iter()
+                .any(|text| text.starts_with("fn add") && text.ends_with('}'))
+        );
+        assert_eq!(selected.last(), Some(&text.to_string()));
+        assert!(selected.windows(2).all(|pair| pair[1].contains(&pair[0])));
+    }
+}
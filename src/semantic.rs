@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use tower_lsp_server::lsp_types::{
+    Position, Range, SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensEdit,
+    SemanticTokensLegend,
+};
+
+use crate::function::Functions;
+use crate::lexer::{self, Token, TokenKind};
+use crate::scope;
+use crate::type_utils::{self, Aliases};
+
+/// Kind of highlighted token, which index is its position in the legend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Function,
+    Jet,
+    Builtin,
+    Parameter,
+    Variable,
+    Alias,
+    BuiltinType,
+    Witness,
+    Param,
+    Number,
+    Keyword,
+    Comment,
+    Namespace,
+    Constructor,
+}
+
+/// Token types of the legend in the order of [`Kind`].
+const TOKEN_TYPES: [SemanticTokenType; 14] = [
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::new("jet"),
+    SemanticTokenType::new("builtin"),
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::new("builtinType"),
+    SemanticTokenType::new("witness"),
+    SemanticTokenType::new("param"),
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::ENUM_MEMBER,
+];
+
+/// Token modifiers of the legend, which bit is the index.
+const TOKEN_MODIFIERS: [SemanticTokenModifier; 2] = [
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::DOCUMENTATION,
+];
+
+const DECLARATION: u32 = 1;
+const DOCUMENTATION: u32 = 1 << 1;
+
+const KEYWORDS: [&str; 8] = [
+    "fn", "let", "match", "mod", "const", "type", "true", "false",
+];
+
+/// Builtin functions, which are called with `(`, `!` or generic arguments after `::`.
+const BUILTINS: [&str; 12] = [
+    "unwrap_left",
+    "unwrap_right",
+    "unwrap",
+    "is_none",
+    "assert",
+    "panic",
+    "dbg",
+    "fold",
+    "array_fold",
+    "for_while",
+    "into",
+    "list",
+];
+
+const BUILTIN_TYPES: [&str; 13] = [
+    "u1", "u2", "u4", "u8", "u16", "u32", "u64", "u128", "u256", "bool", "Either", "Option", "List",
+];
+
+const CONSTRUCTORS: [&str; 4] = ["Some", "None", "Left", "Right"];
+
+/// Token of the document with its kind and modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Highlight {
+    start: Position,
+    length: u32,
+    kind: Kind,
+    modifiers: u32,
+}
+
+/// Semantic tokens sent to the client, which later delta requests refer to by `result_id`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedTokens {
+    pub result_id: String,
+    /// Version of the document the tokens were computed for.
+    pub version: Option<i32>,
+    pub data: Vec<SemanticToken>,
+}
+
+/// Return legend of token types and modifiers used by [`semantic_tokens`].
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Return semantic tokens of the document, encoded relative to the previous token.
+///
+/// Tokens are classified lexically, so files which can't be parsed are highlighted as well.
+pub fn semantic_tokens(text: &str, functions: &Functions, aliases: &Aliases) -> Vec<SemanticToken> {
+    encode(&highlights(text, functions, aliases))
+}
+
+/// Return semantic tokens of the document overlapping `range`, encoded relative to the previous
+/// token of the range.
+pub fn semantic_tokens_in_range(
+    text: &str,
+    functions: &Functions,
+    aliases: &Aliases,
+    range: Range,
+) -> Vec<SemanticToken> {
+    let highlights: Vec<Highlight> = highlights(text, functions, aliases)
+        .into_iter()
+        .filter(|highlight| {
+            let end = Position::new(
+                highlight.start.line,
+                highlight.start.character + highlight.length,
+            );
+            highlight.start < range.end && range.start < end
+        })
+        .collect();
+    encode(&highlights)
+}
+
+/// Return edits turning `previous` tokens into `current` ones, which replace the tokens between
+/// the common prefix and the common suffix.
+///
+/// Indices of the edits count integers of the encoded array, five for every token.
+pub fn delta(previous: &[SemanticToken], current: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    let deleted = previous.len() - prefix - suffix;
+    let inserted = &current[prefix..current.len() - suffix];
+    if deleted == 0 && inserted.is_empty() {
+        return vec![];
+    }
+    let (Ok(start), Ok(delete_count)) = (u32::try_from(prefix * 5), u32::try_from(deleted * 5))
+    else {
+        return vec![];
+    };
+    vec![SemanticTokensEdit {
+        start,
+        delete_count,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+/// Encode highlights as positions relative to the previous highlight.
+fn encode(highlights: &[Highlight]) -> Vec<SemanticToken> {
+    let mut previous = Position::new(0, 0);
+    highlights
+        .iter()
+        .map(|highlight| {
+            let delta_line = highlight.start.line - previous.line;
+            let delta_start = if delta_line == 0 {
+                highlight.start.character - previous.character
+            } else {
+                highlight.start.character
+            };
+            previous = highlight.start;
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: highlight.length,
+                token_type: highlight.kind as u32,
+                token_modifiers_bitset: highlight.modifiers,
+            }
+        })
+        .collect()
+}
+
+/// Return highlights of the document in order of their positions.
+fn highlights(text: &str, functions: &Functions, aliases: &Aliases) -> Vec<Highlight> {
+    let tokens = lexer::tokenize(text);
+    let significant = lexer::significant(&tokens);
+    let classified = classify(text, &significant, functions, aliases);
+
+    let mut highlights = Vec::new();
+    let mut classified = classified.into_iter().peekable();
+    for token in &tokens {
+        let (kind, modifiers) = match token.kind {
+            TokenKind::Comment => (Kind::Comment, 0),
+            TokenKind::DocComment => (Kind::Comment, DOCUMENTATION),
+            TokenKind::Whitespace => continue,
+            _ => match classified.next_if(|(offset, ..)| *offset == token.offset) {
+                Some((_, kind, modifiers)) => (kind, modifiers),
+                None => continue,
+            },
+        };
+        highlights.extend(split_lines(token, kind, modifiers));
+    }
+    highlights
+}
+
+/// Return highlights of every line of the token, since tokens may not span several lines.
+///
+/// Lengths count UTF-16 code units like the columns of token positions.
+fn split_lines(token: &Token, kind: Kind, modifiers: u32) -> Vec<Highlight> {
+    token
+        .text
+        .split('\n')
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let length = u32::try_from(utf16_len(line.trim_end_matches('\r'))).ok()?;
+            let line_number = token.start.line + u32::try_from(index).ok()?;
+            let character = if index == 0 { token.start.character } else { 0 };
+            (length > 0).then_some(Highlight {
+                start: Position::new(line_number, character),
+                length,
+                kind,
+                modifiers,
+            })
+        })
+        .collect()
+}
+
+/// Return number of UTF-16 code units of the text.
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+/// Return byte offsets of classified significant tokens with their kinds and modifiers.
+fn classify(
+    text: &str,
+    tokens: &[Token],
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Vec<(usize, Kind, u32)> {
+    let constants: HashMap<Position, Kind> = scope::module_constants(text, "witness")
+        .into_iter()
+        .map(|binding| (binding.position, Kind::Witness))
+        .chain(
+            scope::module_constants(text, "param")
+                .into_iter()
+                .map(|binding| (binding.position, Kind::Param)),
+        )
+        .collect();
+
+    // Parameters and variables of the current function, which are declared before their use.
+    // Every `{` opens a scope whose names are dropped at the matching `}`, so the first scope
+    // holds parameters.
+    let mut scopes: Vec<HashMap<&str, Kind>> = vec![HashMap::new()];
+    let mut depth = 0usize;
+    let mut signature = false;
+    let mut pattern = false;
+
+    let mut result = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|previous| tokens[previous].text);
+        let next = tokens.get(index + 1).map(|next| next.text);
+
+        let highlight = match token.text {
+            "{" | "(" | "[" => {
+                if token.is("{") {
+                    if depth == 0 {
+                        signature = false;
+                    }
+                    scopes.push(HashMap::new());
+                }
+                depth += 1;
+                None
+            }
+            "}" | ")" | "]" => {
+                depth = depth.saturating_sub(1);
+                if token.is("}") {
+                    if depth == 0 {
+                        scopes.truncate(1);
+                        scopes[0].clear();
+                    } else if scopes.len() > 1 {
+                        scopes.pop();
+                    }
+                }
+                None
+            }
+            ":" | "=" => {
+                pattern = false;
+                None
+            }
+            "fn" if depth == 0 => {
+                scopes.truncate(1);
+                scopes[0].clear();
+                signature = true;
+                Some((Kind::Keyword, 0))
+            }
+            "let" => {
+                pattern = true;
+                Some((Kind::Keyword, 0))
+            }
+            "_" => None,
+            name if token.kind == TokenKind::Ident => {
+                let parameter = signature && depth == 1 && next == Some(":");
+                let variable = depth > 0
+                    && !signature
+                    && (pattern || (previous == Some("(") && next == Some(":")));
+                if parameter || variable {
+                    let kind = if parameter {
+                        Kind::Parameter
+                    } else {
+                        Kind::Variable
+                    };
+                    if let Some(scope) = scopes.last_mut() {
+                        scope.insert(name, kind);
+                    }
+                    Some((kind, DECLARATION))
+                } else {
+                    // Paths and calls are never parameters or variables.
+                    let local = scopes
+                        .iter()
+                        .rev()
+                        .find_map(|scope| scope.get(name).copied())
+                        .filter(|_| {
+                            previous != Some("::") && !matches!(next, Some("(" | "::" | "!"))
+                        });
+                    match local {
+                        Some(kind) => Some((kind, 0)),
+                        None => ident_kind(tokens, index, &constants, functions, aliases),
+                    }
+                }
+            }
+            _ if token.kind == TokenKind::Number => Some((Kind::Number, 0)),
+            _ => None,
+        };
+
+        if let Some((kind, modifiers)) = highlight {
+            result.push((token.offset, kind, modifiers));
+        }
+    }
+    result
+}
+
+/// Return kind of the identifier at `tokens[index]`, which is not a parameter or a variable.
+fn ident_kind(
+    tokens: &[Token],
+    index: usize,
+    constants: &HashMap<Position, Kind>,
+    functions: &Functions,
+    aliases: &Aliases,
+) -> Option<(Kind, u32)> {
+    let token = tokens[index];
+    let name = token.text;
+    let previous = index.checked_sub(1).map(|previous| tokens[previous].text);
+    let next = tokens.get(index + 1).map(|next| next.text);
+
+    if previous == Some("::") {
+        let module = index.checked_sub(2).map(|module| tokens[module].text);
+        let kind = match module {
+            Some("jet") => Kind::Jet,
+            Some("witness") => Kind::Witness,
+            Some("param") => Kind::Param,
+            // Type cast `<T>::into`.
+            _ if BUILTINS.contains(&name) => Kind::Builtin,
+            _ => return None,
+        };
+        return Some((kind, 0));
+    }
+
+    let kind = match previous {
+        Some("mod") => Some(Kind::Namespace),
+        Some("fn") => Some(Kind::Function),
+        Some("type") => Some(Kind::Alias),
+        Some("const") => constants.get(&token.start).copied(),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        return Some((kind, DECLARATION));
+    }
+
+    let kind = if matches!(name, "jet" | "witness" | "param") && next == Some("::") {
+        Kind::Namespace
+    } else if KEYWORDS.contains(&name) {
+        Kind::Keyword
+    } else if BUILTINS.contains(&name) && matches!(next, Some("(" | "!" | "::")) {
+        Kind::Builtin
+    } else if CONSTRUCTORS.contains(&name) {
+        Kind::Constructor
+    } else if functions.get_func(name).is_some() || next == Some("(") {
+        Kind::Function
+    } else if BUILTIN_TYPES.contains(&name) || type_utils::builtin_alias(name).is_some() {
+        Kind::BuiltinType
+    } else if aliases.contains_key(name) {
+        Kind::Alias
+    } else {
+        return None;
+    };
+    Some((kind, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode tokens into text, token type and modifiers of every highlight.
+    fn decode(text: &str, aliases: &Aliases) -> Vec<(String, String, u32)> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut position = Position::new(0, 0);
+        semantic_tokens(text, &Functions::new(), aliases)
+            .into_iter()
+            .map(|token| {
+                if token.delta_line == 0 {
+                    position.character += token.delta_start;
+                } else {
+                    position = Position::new(position.line + token.delta_line, token.delta_start);
+                }
+                let highlighted: String = lines[position.line as usize]
+                    .chars()
+                    .skip(position.character as usize)
+                    .take(token.length as usize)
+                    .collect();
+                (
+                    highlighted,
+                    TOKEN_TYPES[token.token_type as usize].as_str().to_string(),
+                    token.token_modifiers_bitset,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_semantic_tokens_in_range() {
+        let text = "fn main() {\n    let a: u8 = 1;\n    let b: u8 = a;\n}";
+        let range = Range::new(Position::new(2, 0), Position::new(3, 0));
+        let tokens = semantic_tokens_in_range(text, &Functions::new(), &Aliases::new(), range);
+
+        // `let`, `b`, `u8` and `a` of the third line, the first one relative to the start.
+        assert_eq!(tokens.len(), 4);
+        assert_eq!((tokens[0].delta_line, tokens[0].delta_start), (2, 4));
+        assert_eq!(tokens[3].token_type, Kind::Variable as u32);
+    }
+
+    #[test]
+    fn test_semantic_tokens_utf16() {
+        let text = "// \u{1d53d}\u{1d53d}\nfn main() {}";
+        let tokens = semantic_tokens(text, &Functions::new(), &Aliases::new());
+
+        // Characters outside the basic plane take two UTF-16 code units.
+        assert_eq!(tokens[0].length, 7);
+        assert_eq!((tokens[1].delta_line, tokens[1].delta_start), (1, 0));
+    }
+
+    #[test]
+    fn test_semantic_tokens_scopes() {
+        let text = "fn main() {
+    let a: u8 = 1;
+    let c: u8 = {
+        let b: u8 = a;
+        b
+    };
+    let d: u8 = b;
+}";
+        let decoded = decode(text, &Aliases::new());
+        let found = |name: &str| -> Vec<(String, u32)> {
+            decoded
+                .iter()
+                .filter(|(highlighted, ..)| highlighted == name)
+                .map(|(_, ty, modifiers)| (ty.clone(), *modifiers))
+                .collect()
+        };
+
+        let variable = "variable".to_string();
+        assert_eq!(
+            found("a"),
+            [(variable.clone(), DECLARATION), (variable.clone(), 0)]
+        );
+        // `b` is out of scope after the block.
+        assert_eq!(found("b"), [(variable.clone(), DECLARATION), (variable, 0)]);
+    }
+
+    #[test]
+    fn test_delta() {
+        let before = "fn main() {\n    let a: u8 = 1;\n}";
+        let after = "fn main() {\n    let a: u8 = 0x01;\n}";
+        let previous = semantic_tokens(before, &Functions::new(), &Aliases::new());
+        let current = semantic_tokens(after, &Functions::new(), &Aliases::new());
+
+        let edits = delta(&previous, &current);
+        assert_eq!(edits.len(), 1);
+        assert_eq!((edits[0].start, edits[0].delete_count), (25, 5));
+        assert_eq!(edits[0].data.as_deref(), Some(&current[5..6]));
+        assert!(delta(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let text = "mod witness {
+    const SIG: Signature = 0x00;
+}
+
+type Amount = u64;
+
+/// Check the amount.
+fn check(amount: Amount, limit: u64) -> bool {
+    let (carry, diff): (bool, u64) = jet::subtract_64(limit, amount);
+    match carry {
+        true => false,
+        false => jet::le_64(1, diff),
+    }
+}
+
+fn main() {
+    let ok: bool = check(witness::SIG, 5);
+    assert!(ok);
+}";
+        let mut aliases = Aliases::new();
+        aliases.insert("Amount".to_string(), "u64".to_string());
+        let decoded = decode(text, &aliases);
+
+        let cases: [(&str, &[(&str, u32)]); 12] = [
+            ("witness", &[("namespace", DECLARATION), ("namespace", 0)]),
+            ("SIG", &[("witness", DECLARATION), ("witness", 0)]),
+            ("Amount", &[("type", DECLARATION), ("type", 0)]),
+            ("Signature", &[("builtinType", 0)]),
+            ("/// Check the amount.", &[("comment", DOCUMENTATION)]),
+            ("check", &[("function", DECLARATION), ("function", 0)]),
+            ("amount", &[("parameter", DECLARATION), ("parameter", 0)]),
+            ("diff", &[("variable", DECLARATION), ("variable", 0)]),
+            ("subtract_64", &[("jet", 0)]),
+            ("assert", &[("builtin", 0)]),
+            ("0x00", &[("number", 0)]),
+            ("ok", &[("variable", DECLARATION), ("variable", 0)]),
+        ];
+        for (name, expected) in cases {
+            let found: Vec<(&str, u32)> = decoded
+                .iter()
+                .filter(|(highlighted, ..)| highlighted == name)
+                .map(|(_, ty, modifiers)| (ty.as_str(), *modifiers))
+                .collect();
+            assert_eq!(found, expected, "{name}");
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use ropey::Rope;
+use simplicityhl::parse::{self, ParseFromStr};
+use tower_lsp_server::lsp_types::Position;
+
+use crate::function::Functions;
+use crate::utils::{byte_to_position, get_comments_from_lines};
+
+/// Parse the program and return its functions with their doc comments.
+pub fn functions_from(text: &str) -> Functions {
+    let program = parse::Program::parse_from_str(text).expect("Expected valid program");
+    let rope = Rope::from_str(text);
+
+    let mut functions = Functions::new();
+    for item in program.items() {
+        if let parse::Item::Function(func) = item {
+            let line = u32::try_from(func.span().start.line.get()).unwrap() - 1;
+            functions.insert(
+                func.name().to_string(),
+                func.clone(),
+                get_comments_from_lines(line, &rope),
+            );
+        }
+    }
+    functions
+}
+
+/// Remove the `|` marker from text and return its position.
+pub fn split_at_marker(text: &str) -> (String, Position) {
+    let offset = text.find('|').expect("Expected `|` marker");
+    let text = text.replace('|', "");
+    let position = byte_to_position(&Rope::from_str(&text), offset);
+    (text, position)
+}
@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+
+/// Type aliases declared in the document, mapping alias name to its definition.
+pub type Aliases = HashMap<String, String>;
+
+/// Builtin type aliases of `SimplicityHL` with their structural definitions.
+pub const BUILTIN_ALIASES: [(&str, &str); 24] = [
+    ("Amount1", "Either<(u1, u256), u64>"),
+    ("Asset1", "Either<(u1, u256), u256>"),
+    ("Confidential1", "(u1, u256)"),
+    ("Ctx8", "(List<u8, 64>, (u64, u256))"),
+    ("Distance", "u16"),
+    ("Duration", "u16"),
+    ("ExplicitAmount", "u64"),
+    ("ExplicitAsset", "u256"),
+    ("ExplicitNonce", "u256"),
+    ("Fe", "u256"),
+    ("Ge", "(u256, u256)"),
+    ("Gej", "((u256, u256), u256)"),
+    ("Height", "u32"),
+    ("Lock", "u32"),
+    ("Message", "u256"),
+    ("Message64", "[u8; 64]"),
+    ("Nonce", "Either<(u1, u256), u256>"),
+    ("Outpoint", "(u256, u32)"),
+    ("Point", "(u1, u256)"),
+    ("Pubkey", "u256"),
+    ("Scalar", "u256"),
+    ("Signature", "[u8; 64]"),
+    ("Time", "u32"),
+    ("TokenAmount1", "Either<(u1, u256), u64>"),
+];
+
+/// Return structural definition of `SimplicityHL` builtin type alias.
+pub fn builtin_alias(name: &str) -> Option<&'static str> {
+    BUILTIN_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, definition)| *definition)
+}
+
+/// Remove whitespace from type, so types can be compared textually.
+pub fn normalize_type(ty: &str) -> String {
+    ty.chars().filter(|ch| !ch.is_whitespace()).collect()
+}
+
+/// Resolve builtin and document aliases at the top level of the type.
+pub fn resolve_alias(ty: &str, aliases: &Aliases) -> String {
+    let mut resolved = normalize_type(ty);
+    // Bounded number of steps protects from cyclic aliases in broken documents.
+    for _ in 0..16 {
+        let next = aliases
+            .get(&resolved)
+            .map(String::as_str)
+            .or_else(|| builtin_alias(&resolved));
+        match next {
+            Some(next) => resolved = normalize_type(next),
+            None => break,
+        }
+    }
+    resolved
+}
+
+/// Check if two types are the same after resolving aliases.
+pub fn types_match(a: &str, b: &str, aliases: &Aliases) -> bool {
+    let (a, b) = (resolve_alias(a, aliases), resolve_alias(b, aliases));
+    !a.is_empty() && a == b
+}
+
+/// Split text at top-level commas, e.g. `u8, (u16, u32)` into `u8` and `(u16, u32)`.
+pub fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (index, ch) in text.char_indices() {
+        match ch {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(text[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let last = text[start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    parts
+}
+
+/// Return text between the outer brackets, if the whole text is enclosed by `open` and `close`.
+fn strip_enclosing(text: &str, open: char, close: char) -> Option<&str> {
+    let inner = text.trim().strip_prefix(open)?.strip_suffix(close)?;
+
+    let mut depth = 0usize;
+    for ch in inner.chars() {
+        if ch == open {
+            depth += 1;
+        } else if ch == close {
+            // Brackets are closed inside, e.g. `(a) -> (b)`, so the text is not enclosed.
+            depth = depth.checked_sub(1)?;
+        }
+    }
+    Some(inner)
+}
+
+/// Return element types of a tuple type, e.g. `bool` and `u32` for `(bool, u32)`.
+pub fn tuple_elements(ty: &str) -> Option<Vec<String>> {
+    let inner = strip_enclosing(ty, '(', ')')?;
+    Some(
+        split_top_level(inner)
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Return element type and size of an array type, e.g. `u8` and `32` for `[u8; 32]`.
+pub fn array_element(ty: &str) -> Option<(String, String)> {
+    let inner = strip_enclosing(ty, '[', ']')?;
+    let (element, size) = inner.rsplit_once(';')?;
+    Some((element.trim().to_string(), size.trim().to_string()))
+}
+
+/// Return generic arguments of the type `name`, e.g. `u8` and `u16` of `Either<u8, u16>`.
+pub fn generic_arguments(ty: &str, name: &str) -> Option<Vec<String>> {
+    let rest = ty.trim().strip_prefix(name)?;
+    let inner = strip_enclosing(rest, '<', '>')?;
+    Some(
+        split_top_level(inner)
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Structure of a type, which values of all types cast into each other share.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Structure {
+    Unit,
+    Sum(Box<Structure>, Box<Structure>),
+    Product(Box<Structure>, Box<Structure>),
+}
+
+/// Maximum number of nodes of a [`Structure`], which protects from huge arrays.
+const MAX_STRUCTURE_NODES: usize = 1 << 16;
+
+/// Return structure of the type, e.g. `(u4, u4)` for `u8`, or `None` if it is not a valid type.
+pub fn structure(ty: &str, aliases: &Aliases) -> Option<Structure> {
+    let mut budget = MAX_STRUCTURE_NODES;
+    structure_of(ty, aliases, &mut budget)
+}
+
+/// Check if value of type `from` can be cast into type `to` with `<from>::into`.
+pub fn is_castable(from: &str, to: &str, aliases: &Aliases) -> bool {
+    structure(from, aliases).is_some_and(|from| structure(to, aliases) == Some(from))
+}
+
+fn structure_of(ty: &str, aliases: &Aliases, budget: &mut usize) -> Option<Structure> {
+    *budget = budget.checked_sub(1)?;
+    let ty = resolve_alias(ty, aliases);
+
+    match ty.as_str() {
+        "()" => return Some(Structure::Unit),
+        "u1" | "bool" => {
+            return Some(Structure::Sum(
+                Box::new(Structure::Unit),
+                Box::new(Structure::Unit),
+            ));
+        }
+        _ => {}
+    }
+
+    if let Some(bits) = ty
+        .strip_prefix('u')
+        .and_then(|bits| bits.parse::<usize>().ok())
+    {
+        if !bits.is_power_of_two() || !(2..=256).contains(&bits) {
+            return None;
+        }
+        let half = format!("u{}", bits / 2);
+        return Some(Structure::Product(
+            Box::new(structure_of(&half, aliases, budget)?),
+            Box::new(structure_of(&half, aliases, budget)?),
+        ));
+    }
+    if let Some([left, right]) = generic_arguments(&ty, "Either").as_deref() {
+        return Some(Structure::Sum(
+            Box::new(structure_of(left, aliases, budget)?),
+            Box::new(structure_of(right, aliases, budget)?),
+        ));
+    }
+    if let Some([element]) = generic_arguments(&ty, "Option").as_deref() {
+        return Some(Structure::Sum(
+            Box::new(Structure::Unit),
+            Box::new(structure_of(element, aliases, budget)?),
+        ));
+    }
+    if let Some([element, bound]) = generic_arguments(&ty, "List").as_deref() {
+        let bound: usize = bound.parse().ok()?;
+        if !bound.is_power_of_two() || bound < 2 {
+            return None;
+        }
+        if bound == 2 {
+            return structure_of(&format!("Option<{element}>"), aliases, budget);
+        }
+        return Some(Structure::Product(
+            Box::new(structure_of(
+                &format!("Option<[{element}; {}]>", bound / 2),
+                aliases,
+                budget,
+            )?),
+            Box::new(structure_of(
+                &format!("List<{element}, {}>", bound / 2),
+                aliases,
+                budget,
+            )?),
+        ));
+    }
+    if let Some((element, size)) = array_element(&ty) {
+        let size: usize = size.parse().ok()?;
+        if size > *budget {
+            return None;
+        }
+        return tuple_structure(&vec![element; size], aliases, budget);
+    }
+
+    tuple_structure(&tuple_elements(&ty)?, aliases, budget)
+}
+
+/// Return structure of a tuple, which is a balanced tree of products, e.g. `(A, (B, C))`.
+fn tuple_structure(
+    elements: &[String],
+    aliases: &Aliases,
+    budget: &mut usize,
+) -> Option<Structure> {
+    match elements {
+        [] => Some(Structure::Unit),
+        [element] => structure_of(element, aliases, budget),
+        _ => {
+            let (left, right) = elements.split_at(elements.len() / 2);
+            Some(Structure::Product(
+                Box::new(tuple_structure(left, aliases, budget)?),
+                Box::new(tuple_structure(right, aliases, budget)?),
+            ))
+        }
+    }
+}
+
+/// Return zero value of the type, e.g. `0x0000` for `u16` or `(false, None)` for
+/// `(bool, Option<u8>)`.
+pub fn zero_value(ty: &str, aliases: &Aliases) -> Option<String> {
+    let ty = resolve_alias(ty, aliases);
+
+    match ty.as_str() {
+        "()" => return Some("()".to_string()),
+        "bool" => return Some("false".to_string()),
+        "u1" | "u2" | "u4" => return Some("0".to_string()),
+        _ => {}
+    }
+
+    if let Some(bits) = ty
+        .strip_prefix('u')
+        .and_then(|bits| bits.parse::<usize>().ok())
+    {
+        return (bits.is_power_of_two() && (8..=256).contains(&bits))
+            .then(|| format!("0x{}", "0".repeat(bits / 4)));
+    }
+    if generic_arguments(&ty, "Option").is_some() {
+        return Some("None".to_string());
+    }
+    if let Some([left, _]) = generic_arguments(&ty, "Either").as_deref() {
+        return Some(format!("Left({})", zero_value(left, aliases)?));
+    }
+    if generic_arguments(&ty, "List").is_some() {
+        return Some("list![]".to_string());
+    }
+    if let Some((element, size)) = array_element(&ty) {
+        let size: usize = size.parse().ok()?;
+        if resolve_alias(&element, aliases) == "u8" {
+            return Some(format!("0x{}", "00".repeat(size)));
+        }
+        let element = zero_value(&element, aliases)?;
+        return Some(format!("[{}]", vec![element; size].join(", ")));
+    }
+
+    let elements = tuple_elements(&ty)?
+        .iter()
+        .map(|element| zero_value(element, aliases))
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("({})", elements.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_types_match() {
+        let aliases = Aliases::from([("Key".to_string(), "Pubkey".to_string())]);
+
+        assert!(types_match("(bool, u32)", "(bool,u32)", &aliases));
+        assert!(types_match("Key", "u256", &aliases));
+        assert!(types_match("Signature", "[u8; 64]", &aliases));
+        assert!(!types_match("u32", "u64", &aliases));
+        assert!(!types_match("", "", &aliases));
+    }
+
+    #[test]
+    fn test_destructure_types() {
+        assert_eq!(
+            tuple_elements("(bool, (u8, u16))"),
+            Some(vec!["bool".to_string(), "(u8, u16)".to_string()])
+        );
+        assert_eq!(tuple_elements("()"), Some(vec![]));
+        assert_eq!(tuple_elements("(u8) -> (u8)"), None);
+        assert_eq!(
+            array_element("[[u8; 2]; 4]"),
+            Some(("[u8; 2]".to_string(), "4".to_string()))
+        );
+        assert_eq!(
+            split_top_level("Either<u8, u16>, u32"),
+            vec!["Either<u8, u16>", "u32"]
+        );
+        assert_eq!(
+            generic_arguments("Either<(u8, u16), Option<u8>>", "Either"),
+            Some(vec!["(u8, u16)".to_string(), "Option<u8>".to_string()])
+        );
+        assert_eq!(generic_arguments("Option<u8>", "Either"), None);
+    }
+
+    #[test]
+    fn test_is_castable() {
+        let aliases = Aliases::new();
+
+        assert!(is_castable("u8", "(u4, u4)", &aliases));
+        assert!(is_castable("bool", "Either<(), ()>", &aliases));
+        assert!(is_castable("Option<u8>", "Either<(), u8>", &aliases));
+        assert!(is_castable("[u8; 3]", "(u8, (u8, u8))", &aliases));
+        assert!(is_castable(
+            "List<u8, 4>",
+            "(Option<[u8; 2]>, Option<u8>)",
+            &aliases
+        ));
+        assert!(is_castable("Pubkey", "[u8; 32]", &aliases));
+        assert!(!is_castable("u8", "u16", &aliases));
+        assert!(!is_castable("u3", "u3", &aliases));
+    }
+
+    #[test]
+    fn test_zero_value() {
+        let aliases = Aliases::new();
+
+        assert_eq!(zero_value("u16", &aliases), Some("0x0000".to_string()));
+        assert_eq!(
+            zero_value("Signature", &aliases),
+            Some(format!("0x{}", "00".repeat(64)))
+        );
+        assert_eq!(
+            zero_value("(bool, Option<u8>, [u2; 2])", &aliases),
+            Some("(false, None, [0, 0])".to_string())
+        );
+        assert_eq!(zero_value("Foo", &aliases), None);
+    }
+}
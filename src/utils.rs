@@ -19,39 +19,32 @@ pub fn span_contains(a: &simplicityhl::error::Span, b: &simplicityhl::error::Spa
     position_le(&a.start, &b.start) && position_ge(&a.end, &b.end)
 }
 
-/// Convert [`simplicityhl::error::Span`] to [`tower_lsp_server::lsp_types::Position`]
+/// Convert [`simplicityhl::error::Span`] of the document to [`tower_lsp_server::lsp_types::Position`]
 ///
 /// Converting is required because `simplicityhl::error::Span` using their own versions of `Position`,
-/// which contains non-zero column and line, so they are always starts with one.
-/// `Position` required for diagnostic starts with zero
+/// which contains non-zero column and line, so they are always starts with one, and whose column
+/// counts characters. `Position` required for diagnostic starts with zero and its column counts
+/// UTF-16 code units.
 pub fn span_to_positions(
+    rope: &Rope,
     span: &simplicityhl::error::Span,
-) -> Result<(lsp_types::Position, lsp_types::Position), LspError> {
-    let start_line = u32::try_from(span.start.line.get())?;
-    let start_col = u32::try_from(span.start.col.get())?;
-    let end_line = u32::try_from(span.end.line.get())?;
-    let end_col = u32::try_from(span.end.col.get())?;
-
-    Ok((
-        lsp_types::Position {
-            line: start_line - 1,
-            character: start_col - 1,
-        },
-        lsp_types::Position {
-            line: end_line - 1,
-            character: end_col - 1,
-        },
-    ))
+) -> (lsp_types::Position, lsp_types::Position) {
+    (
+        char_to_position(rope, span.start.line.get() - 1, span.start.col.get() - 1),
+        char_to_position(rope, span.end.line.get() - 1, span.end.col.get() - 1),
+    )
 }
 
-/// Convert [`tower_lsp_server::lsp_types::Position`] to [`simplicityhl::error::Span`]
+/// Convert [`tower_lsp_server::lsp_types::Position`] in the document to [`simplicityhl::error::Span`]
 ///
 /// Useful when [`tower_lsp_server::lsp_types::Position`] represents some singular point.
 pub fn position_to_span(
+    rope: &Rope,
     position: lsp_types::Position,
 ) -> Result<simplicityhl::error::Span, LspError> {
-    let start_line = NonZeroUsize::try_from((position.line + 1) as usize)?;
-    let start_col = NonZeroUsize::try_from((position.character + 1) as usize)?;
+    let (line, column) = position_to_char(rope, position);
+    let start_line = NonZeroUsize::try_from(line + 1)?;
+    let start_col = NonZeroUsize::try_from(column + 1)?;
 
     Ok(simplicityhl::error::Span {
         start: simplicityhl::error::Position {
@@ -174,21 +167,12 @@ pub fn find_function_name_range(
         )));
     };
 
-    let func_size = u32::try_from(function.name().as_inner().len()).map_err(LspError::from)?;
-
-    let (line, character) = (
-        u32::try_from(line).map_err(LspError::from)?,
-        u32::try_from(character).map_err(LspError::from)?,
-    );
-
-    let (start, end) = (
-        lsp_types::Position { line, character },
-        lsp_types::Position {
-            line,
-            character: character + func_size,
-        },
-    );
-    Ok(lsp_types::Range { start, end })
+    let start = text.line_to_byte(line) + character;
+    let end = start + function.name().as_inner().len();
+    Ok(lsp_types::Range {
+        start: byte_to_position(text, start),
+        end: byte_to_position(text, end),
+    })
 }
 
 pub fn get_call_span(
@@ -208,9 +192,10 @@ pub fn get_call_span(
 }
 
 pub fn find_all_references<'a>(
+    rope: &Rope,
     functions: &'a [&'a parse::Function],
     call_name: &CallName,
-) -> Result<Vec<lsp_types::Range>, LspError> {
+) -> Vec<lsp_types::Range> {
     functions
         .iter()
         .flat_map(|func| {
@@ -228,10 +213,54 @@ pub fn find_all_references<'a>(
                 .collect::<Vec<_>>()
         })
         .map(|span| {
-            let (start, end) = span_to_positions(&span)?;
-            Ok(lsp_types::Range { start, end })
+            let (start, end) = span_to_positions(rope, &span);
+            lsp_types::Range { start, end }
         })
-        .collect::<Result<Vec<_>, LspError>>()
+        .collect()
+}
+
+/// Return position after the last character of the document.
+pub fn document_end(rope: &Rope) -> lsp_types::Position {
+    byte_to_position(rope, rope.len_bytes())
+}
+
+/// Convert byte offset in the document to [`lsp_types::Position`].
+///
+/// Like every position of this server, its column counts UTF-16 code units, which is the default
+/// encoding of LSP positions.
+pub fn byte_to_position(rope: &Rope, offset: usize) -> lsp_types::Position {
+    let char = rope.byte_to_char(offset.min(rope.len_bytes()));
+    let line = rope.char_to_line(char);
+    char_to_position(rope, line, char - rope.line_to_char(line))
+}
+
+/// Convert [`lsp_types::Position`] to byte offset in the document, clamping it to the document.
+pub fn position_to_byte(rope: &Rope, position: lsp_types::Position) -> usize {
+    let (line, column) = position_to_char(rope, position);
+    rope.char_to_byte(rope.line_to_char(line) + column)
+}
+
+/// Convert zero-based line and character column in the document to [`lsp_types::Position`],
+/// clamping it to the document.
+fn char_to_position(rope: &Rope, line: usize, column: usize) -> lsp_types::Position {
+    let line = line.min(rope.len_lines().saturating_sub(1));
+    let line_start = rope.line_to_char(line);
+    let char = line_start + column.min(rope.line(line).len_chars());
+    let character = rope.char_to_utf16_cu(char) - rope.char_to_utf16_cu(line_start);
+    lsp_types::Position {
+        line: u32::try_from(line).unwrap_or(u32::MAX),
+        character: u32::try_from(character).unwrap_or(u32::MAX),
+    }
+}
+
+/// Return zero-based line and character column of [`lsp_types::Position`] in the document,
+/// clamping it to the document.
+fn position_to_char(rope: &Rope, position: lsp_types::Position) -> (usize, usize) {
+    let line = (position.line as usize).min(rope.len_lines().saturating_sub(1));
+    let line_start = rope.line_to_char(line);
+    let line_end = rope.char_to_utf16_cu(line_start + rope.line(line).len_chars());
+    let utf16 = (rope.char_to_utf16_cu(line_start) + position.character as usize).min(line_end);
+    (line, rope.utf16_cu_to_char(utf16) - line_start)
 }
 
 #[cfg(test)]
@@ -259,4 +288,20 @@ mod tests {
         let result = get_comments_from_lines(0, &text);
         assert_eq!(result, "");
     }
+
+    #[test]
+    fn test_positions_utf16() {
+        // Characters outside the basic plane take two UTF-16 code units and four bytes.
+        let rope = Rope::from_str("/* \u{1d53d} */ fn f() {}\nx");
+        let position = lsp_types::Position::new(0, 9);
+        assert_eq!(byte_to_position(&rope, 11), position);
+        assert_eq!(position_to_byte(&rope, position), 11);
+        assert_eq!(position_to_byte(&rope, lsp_types::Position::new(0, 4)), 3);
+        assert_eq!(document_end(&rope), lsp_types::Position::new(1, 1));
+
+        // Columns of spans count characters.
+        let span = position_to_span(&rope, position).unwrap();
+        assert_eq!(span.start.col.get(), 9);
+        assert_eq!(span_to_positions(&rope, &span), (position, position));
+    }
 }